@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use eui48::MacAddress;
+use netfcts::tcp_common::L234Data;
+
+pub const ETYPE_ARP: u16 = 0x0806;
+pub const ARP_REQUEST: u16 = 1;
+pub const ARP_REPLY: u16 = 2;
+/// size of an ARP packet for IPv4 over Ethernet
+const ARP_LEN: usize = 28;
+/// interval in millis, in which an unresolved or expired address is requested again
+pub const ARP_RETRY_MILLIS: u64 = 1000;
+
+/// ARP packet for IPv4 over Ethernet (RFC 826)
+#[derive(Debug, Clone, Copy)]
+pub struct ArpPacket {
+    pub op: u16,
+    pub sha: MacAddress,
+    pub spa: u32,
+    pub tha: MacAddress,
+    pub tpa: u32,
+}
+
+impl ArpPacket {
+    /// parses the payload of the Ethernet header, returns None for anything else than IPv4 over Ethernet
+    pub fn parse(buf: &[u8]) -> Option<ArpPacket> {
+        if buf.len() < ARP_LEN || buf[0..6] != [0x00, 0x01, 0x08, 0x00, 6, 4] {
+            return None;
+        }
+        Some(ArpPacket {
+            op: u16::from_be_bytes([buf[6], buf[7]]),
+            sha: MacAddress::from_bytes(&buf[8..14]).unwrap(),
+            spa: u32::from_be_bytes([buf[14], buf[15], buf[16], buf[17]]),
+            tha: MacAddress::from_bytes(&buf[18..24]).unwrap(),
+            tpa: u32::from_be_bytes([buf[24], buf[25], buf[26], buf[27]]),
+        })
+    }
+
+    /// writes the packet into the payload of the Ethernet header, remaining bytes of buf are padding
+    pub fn write(&self, buf: &mut [u8]) {
+        buf[0..6].copy_from_slice(&[0x00, 0x01, 0x08, 0x00, 6, 4]);
+        buf[6..8].copy_from_slice(&self.op.to_be_bytes());
+        buf[8..14].copy_from_slice(self.sha.as_bytes());
+        buf[14..18].copy_from_slice(&self.spa.to_be_bytes());
+        buf[18..24].copy_from_slice(self.tha.as_bytes());
+        buf[24..28].copy_from_slice(&self.tpa.to_be_bytes());
+    }
+}
+
+/// MAC addresses of the targets with the time stamp of the last ARP packet from the target,
+/// shared by all pipelines, as ARP packets may arrive on any queue of the port
+#[derive(Clone, Default)]
+pub struct NeighbourCache(Arc<RwLock<HashMap<u32, (MacAddress, u64)>>>);
+
+impl NeighbourCache {
+    #[inline]
+    fn get(&self, ip: u32) -> Option<(MacAddress, u64)> {
+        self.0.read().unwrap().get(&ip).cloned()
+    }
+
+    #[inline]
+    fn insert(&self, ip: u32, mac: MacAddress, now: u64) {
+        self.0.write().unwrap().insert(ip, (mac, now));
+    }
+}
+
+struct Neighbour {
+    /// the target or the gateway, through which the target is reached
+    next_hop: u32,
+    /// index of the target in the servers
+    index: usize,
+    /// time stamp of our last request
+    requested: Option<u64>,
+}
+
+/// the targets of a pipeline, which are resolved with ARP, i.e. targets without static MAC or linux i/f,
+/// we resolve the next hop of a target, the MAC of a routed target is the one of its gateway, all times in cycles
+pub struct NeighbourTable {
+    cache: NeighbourCache,
+    neighbours: Vec<Neighbour>,
+    /// time after which a resolved address is requested again
+    lifetime: u64,
+    retry: u64,
+}
+
+impl NeighbourTable {
+    /// next_hops has the same index as servers
+    pub fn new(
+        cache: NeighbourCache,
+        servers: &Vec<L234Data>,
+        next_hops: &Vec<u32>,
+        lifetime: u64,
+        retry: u64,
+    ) -> NeighbourTable {
+        NeighbourTable {
+            cache,
+            neighbours: servers
+                .iter()
+                .enumerate()
+                .filter(|(_, server)| server.mac.is_nil())
+                .map(|(index, _)| Neighbour {
+                    next_hop: next_hops[index],
+                    index,
+                    requested: None,
+                })
+                .collect(),
+            lifetime,
+            retry,
+        }
+    }
+
+    /// learns the MAC of a next hop from requests, replies and gratuitous ARP, so we follow a failover of the DUT
+    pub fn learn(&self, arp: &ArpPacket, now: u64) {
+        if self.neighbours.iter().any(|n| n.next_hop == arp.spa) {
+            self.cache.insert(arp.spa, arp.sha, now);
+        }
+    }
+
+    /// copies the learned MACs into the servers, returns the addresses which must be requested,
+    /// because they are not yet resolved or their lifetime expired, a next hop of several targets is requested once
+    pub fn refresh(&mut self, servers: &mut Vec<L234Data>, now: u64) -> Vec<u32> {
+        let mut requests = Vec::new();
+        let retry = self.retry;
+        for n in self.neighbours.iter_mut() {
+            if let Some((mac, stamp)) = self.cache.get(n.next_hop) {
+                servers[n.index].mac = mac;
+                if now.wrapping_sub(stamp) < self.lifetime {
+                    continue;
+                }
+            }
+            if n.requested.map_or(true, |requested| now.wrapping_sub(requested) >= retry) {
+                n.requested = Some(now);
+                if !requests.contains(&n.next_hop) {
+                    requests.push(n.next_hop);
+                }
+            }
+        }
+        requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GATEWAY: u32 = 0x0a00_0001;
+    const ROUTED_1: u32 = 0x0a01_0001;
+    const ROUTED_2: u32 = 0x0a01_0002;
+    const LOCAL: u32 = 0x0a00_0020;
+
+    fn server(mac: MacAddress, ip: u32, index: usize) -> L234Data {
+        L234Data {
+            mac,
+            ip,
+            port: 80,
+            server_id: format!("server {}", index),
+            index,
+        }
+    }
+
+    fn reply(sha: MacAddress, spa: u32) -> ArpPacket {
+        ArpPacket {
+            op: ARP_REPLY,
+            sha,
+            spa,
+            tha: MacAddress::new([0xa0, 0x36, 0x9f, 0x82, 0x9c, 0xfc]),
+            tpa: 0x0a00_0002,
+        }
+    }
+
+    #[test]
+    fn arp_packet_round_trip() {
+        let packet = reply(MacAddress::new([0x3c, 0xfd, 0xfe, 0x9e, 0xce, 0x4c]), GATEWAY);
+        let mut buf = [0xffu8; 46];
+        packet.write(&mut buf);
+        assert_eq!(&buf[0..8], &[0x00, 0x01, 0x08, 0x00, 6, 4, 0, 2]);
+        let parsed = ArpPacket::parse(&buf).unwrap();
+        assert_eq!(parsed.op, ARP_REPLY);
+        assert_eq!(parsed.sha, packet.sha);
+        assert_eq!(parsed.spa, GATEWAY);
+        assert_eq!(parsed.tha, packet.tha);
+        assert_eq!(parsed.tpa, packet.tpa);
+        // truncated or not IPv4 over Ethernet
+        assert!(ArpPacket::parse(&buf[..27]).is_none());
+        buf[2] = 0x86;
+        buf[3] = 0xdd;
+        assert!(ArpPacket::parse(&buf).is_none());
+    }
+
+    #[test]
+    fn refresh_requests_unresolved_next_hops() {
+        let static_mac = MacAddress::new([0x02, 0, 0, 0, 0, 1]);
+        let gateway_mac = MacAddress::new([0x02, 0, 0, 0, 0, 2]);
+        let mut servers = vec![
+            server(static_mac, LOCAL, 0),
+            server(MacAddress::nil(), ROUTED_1, 1),
+            server(MacAddress::nil(), ROUTED_2, 2),
+            server(MacAddress::nil(), LOCAL + 1, 3),
+        ];
+        let next_hops = vec![LOCAL, GATEWAY, GATEWAY, LOCAL + 1];
+        let cache = NeighbourCache::default();
+        let mut table = NeighbourTable::new(cache.clone(), &servers, &next_hops, 1000, 100);
+        // the shared gateway is requested once, the target with a static MAC not at all
+        assert_eq!(table.refresh(&mut servers, 0), vec![GATEWAY, LOCAL + 1]);
+        assert!(table.refresh(&mut servers, 50).is_empty());
+        assert_eq!(table.refresh(&mut servers, 100), vec![GATEWAY, LOCAL + 1]);
+
+        // only next hops are learned
+        table.learn(&reply(gateway_mac, GATEWAY), 200);
+        table.learn(&reply(gateway_mac, ROUTED_1), 200);
+        assert_eq!(table.refresh(&mut servers, 200), vec![LOCAL + 1]);
+        assert_eq!(servers[0].mac, static_mac);
+        assert_eq!(servers[1].mac, gateway_mac);
+        assert_eq!(servers[2].mac, gateway_mac);
+        assert!(servers[3].mac.is_nil());
+
+        // the cache is shared with the tables of the other pipelines
+        let mut other_servers = servers.clone();
+        other_servers[1].mac = MacAddress::nil();
+        let mut other_table = NeighbourTable::new(cache, &other_servers, &next_hops, 1000, 100);
+        other_table.refresh(&mut other_servers, 200);
+        assert_eq!(other_servers[1].mac, gateway_mac);
+
+        // an expired address is requested again, but used until it is refreshed
+        assert!(table.refresh(&mut servers, 1100).iter().all(|&ip| ip != GATEWAY));
+        assert_eq!(table.refresh(&mut servers, 1200), vec![GATEWAY, LOCAL + 1]);
+        assert_eq!(servers[1].mac, gateway_mac);
+    }
+}
@@ -17,6 +17,7 @@ use e2d2::interface::{ PmdPort, Pdu, HeaderStack};
 use e2d2::native::zcsi::*;
 use e2d2::scheduler::StandaloneScheduler;
 use e2d2::utils;
+use eui48::MacAddress;
 
 use netfcts::comm::{MessageFrom, MessageTo};
 use netfcts::comm::PipelineId;
@@ -26,12 +27,11 @@ use netfcts::conrecord::{ConRecord, HasTcpState, HasConData};
 #[cfg(feature = "profiling")]
 use netfcts::io::print_rx_tx_counters;
 use netfcts::tcp_common::{CData, tcp_payload_size};
-use netfcts::{strip_payload, RecordStore};
-use netfcts::recstore::TEngineStore;
+use netfcts::strip_payload;
 
 use netfcts::RunTime;
 
-use traffic_lib::{setup_pipelines, Connection, Configuration};
+use traffic_lib::{setup_pipelines, Connection, Configuration, EngineRecords};
 
 use traffic_lib::L234Data;
 use traffic_lib::ReleaseCause;
@@ -101,8 +101,8 @@ fn print_performance_from_stamps(cpu_clock: u64, nr_connections: usize, start_st
 }
 
 fn evaluate_records(
-    con_records_c: &mut Vec<(PipelineId, RecordStore<ConRecord>)>,
-    con_records_s: &mut Vec<(PipelineId, RecordStore<ConRecord>)>,
+    con_records_c: &Vec<(PipelineId, EngineRecords)>,
+    con_records_s: &Vec<(PipelineId, EngineRecords)>,
     cpu_clock: u64,
 ) {
     println!("\nperformance data derived from connection records:");
@@ -118,7 +118,7 @@ fn evaluate_records(
     let mut total_connections = 0;
     {
         let cc = &(con_records_c[0].1);
-        min_total = cc.iter().last().unwrap().clone();
+        min_total = cc.con_records.iter().last().unwrap().clone();
         max_total = min_total.clone();
     }
 
@@ -126,30 +126,36 @@ fn evaluate_records(
     let mut by_uuid = HashMap::with_capacity(con_records_s[0].1.len() * con_records_s.len());
     let mut completed_count_s = 0;
     for (_p, c_records_server) in con_records_s {
-        c_records_server.iter().enumerate().for_each(|(_i, c)| {
+        c_records_server.iter().for_each(|(c, x)| {
             if c.release_cause() == ReleaseCause::ActiveClose && c.states().last().unwrap() == &TcpState::Closed {
                 completed_count_s += 1
             };
-            by_uuid.insert(c.uid(), c);
+            by_uuid.insert(c.uid(), (c, x));
         });
     }
 
-    for (p, c_records_client) in con_records_c {
+    for (p, records_client) in con_records_c {
         f.write_all(format!("Pipeline {}:\n", p).as_bytes())
             .expect("cannot write c_records");
         let mut completed_count_c = 0;
-        c_records_client.sort_by(|a, b| a.port().cmp(&b.port()));
+        // the extensions are sorted together with their ConRecords
+        let mut c_records_client: Vec<_> = records_client.iter().collect();
+        c_records_client.sort_by(|a, b| a.0.port().cmp(&b.0.port()));
         if c_records_client.len() > 0 {
             total_connections += c_records_client.len();
-            let mut min = c_records_client.iter().last().unwrap();
+            let mut min = c_records_client.last().unwrap().0;
             let mut max = min;
-            c_records_client.iter().enumerate().for_each(|(i, c)| {
+            c_records_client.iter().enumerate().for_each(|(i, &(c, x))| {
                 let uuid = c.uid();
                 let c_server = by_uuid.remove(&uuid);
                 let line = format!("{:6}: {}\n", i, c);
                 f.write_all(line.as_bytes()).expect("cannot write c_records");
+                if let Some(x) = x {
+                    f.write_all(format!("        {}\n", x).as_bytes())
+                        .expect("cannot write c_records");
+                }
                 if c_server.is_some() {
-                    let c_server = c_server.unwrap();
+                    let (c_server, x_server) = c_server.unwrap();
                     let line = format!(
                         "        ({:?}, {:21}, {:6}, {:3}, {:7}, {:7}, {:?}, {:?}, +{}, {:?})\n",
                         c_server.role(),
@@ -173,6 +179,10 @@ fn evaluate_records(
                             .collect::<Vec<_>>(),
                     );
                     f.write_all(line.as_bytes()).expect("cannot write c_records");
+                    if let Some(x_server) = x_server {
+                        f.write_all(format!("        {}\n", x_server).as_bytes())
+                            .expect("cannot write c_records");
+                    }
                 }
                 if (c.release_cause() == ReleaseCause::PassiveClose || c.release_cause() == ReleaseCause::ActiveClose)
                     && c.states().last().unwrap() == &TcpState::Closed
@@ -209,7 +219,7 @@ fn evaluate_records(
     println!("total completed server connections = {}", completed_count_s);
 
     println!("unbound server-side connections = {}", by_uuid.len());
-    by_uuid.iter().enumerate().for_each(|(i, (_, c))| {
+    by_uuid.iter().enumerate().for_each(|(i, (_, (c, _)))| {
         debug!("{:6}: {}", i, c);
     });
 
@@ -228,7 +238,7 @@ fn evaluate_records(
 }
 
 pub fn main() {
-    let mut run_time: RunTime<Configuration, TEngineStore> = match RunTime::init() {
+    let mut run_time: RunTime<Configuration, EngineRecords> = match RunTime::init() {
         Ok(run_time) => run_time,
         Err(err) => panic!("failed to initialize RunTime {}", err),
     };
@@ -238,6 +248,14 @@ pub fn main() {
     run_time.setup_flowdirector().expect("failed to setup flowdirector");
 
     let run_configuration = run_time.run_configuration.clone();
+    for port in run_time.context().expect("no context").ports.values().filter(|p| p.is_physical()) {
+        if let Some(flow_steering) = port.flow_steering_mode() {
+            if let Err(e) = run_configuration.engine_configuration.engine.check_client_pool(flow_steering) {
+                error!("{} in configuration file {}", e, run_time.toml_filename());
+                std::process::exit(1);
+            }
+        }
+    }
 
     // number of payloads sent, after which the connection is closed
     let fin_by_client = run_configuration.engine_configuration.engine.fin_by_client.unwrap_or(1000);
@@ -259,7 +277,8 @@ pub fn main() {
         .map(|(i, srv_cfg)| L234Data {
             mac: srv_cfg
                 .mac
-                .unwrap_or_else(|| get_mac_from_ifname(srv_cfg.linux_if.as_ref().unwrap()).unwrap()),
+                .or_else(|| srv_cfg.linux_if.as_ref().map(|linux_if| get_mac_from_ifname(linux_if).unwrap()))
+                .unwrap_or(MacAddress::nil()), // resolved with ARP by the pipelines
             ip: u32::from(srv_cfg.ip),
             port: srv_cfg.port,
             server_id: srv_cfg.id.clone(),
@@ -416,8 +435,8 @@ pub fn main() {
         .unwrap_or(false)
     {
         evaluate_records(
-            &mut con_records_c,
-            &mut con_records_s,
+            &con_records_c,
+            &con_records_s,
             run_configuration.system_data.cpu_clock,
         );
     }
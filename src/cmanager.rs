@@ -1,21 +1,28 @@
 use std::net::Ipv4Addr;
 use std::collections::VecDeque;
-//use std::collections::HashMap;
+use std::collections::HashMap;
 //use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicUsize, Ordering, };
 use std::fmt;
 use std::mem;
+use std::cmp;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use e2d2::interface::{PortQueue, L4Flow};
+use eui48::MacAddress;
+use ipnet::Ipv4Net;
 use netfcts::timer_wheel::TimerWheel;
 use PipelineId;
+use retransmission::{Segment, RttEstimator, RtoConfig, seq_lt, seq_leq, seq_gt, SEG_SYN, SEG_ACK};
+use congestion::{CongestionControl, CongestionControlType, new_congestion_control, DUP_ACK_THRESHOLD};
+use tcp_options::{NegotiatedOptions, SackBlocks, OptionBuf};
+use reassembly::ReassemblyQueue;
 
 use netfcts::tcp_common::*;
-use netfcts::conrecord::{ConRecord, HasTcpState};
-use netfcts::utils::shuffle_ports;
+use netfcts::conrecord::ConRecord;
+use netfcts::utils::{shuffle_ports, Sock2Index};
 use netfcts::{RecordStore, ConRecordOperations};
 use netfcts::recstore::TEngineStore;
 
@@ -27,10 +34,70 @@ pub struct Connection {
     pub wheel_slot_and_index: (u16, u16),
     /// next client side sequence no towards DUT
     pub seqn_nxt: u32,
-    // oldest unacknowledged sequence no
-    //    pub seqn_una: u32,
+    /// oldest unacknowledged sequence no
+    pub seqn_una: u32,
     /// current ack no towards DUT (expected seqn)
     pub ackn_nxt: u32,
+    /// initial sequence no of the DUT, i.e. the seqn of its SYN or SYN-ACK
+    pub irs: u32,
+    /// send window, as advertised by the DUT
+    pub snd_wnd: u32,
+    /// seqn and ackn of the segment which was used for the last window update
+    snd_wl1: u32,
+    snd_wl2: u32,
+    /// receive window, which we advertise to the DUT
+    pub rcv_wnd: u32,
+    /// result of the option negotiation in the handshake
+    pub options: NegotiatedOptions,
+    /// out-of-order data which we received from the DUT, reported in our SACK option
+    pub sack_blocks: SackBlocks,
+    /// out-of-order payload of the DUT
+    reassembly: ReassemblyQueue,
+    /// set, if a payload segment was held back, because the send window was exhausted or by Nagle's algorithm
+    wnd_blocked: bool,
+    /// application message, which is sent in segments of at most one MSS
+    message: Vec<u8>,
+    /// number of bytes of the message, which are already sent
+    message_sent: usize,
+    /// actively closed connection, which holds its port for the duration of TIME_WAIT
+    time_wait: bool,
+    /// congestion control, only used by the client
+    cc: Option<Box<dyn CongestionControl>>,
+    dup_acks: u8,
+    /// highest seqn sent when we entered fast recovery (RFC 6582), None if not in fast recovery
+    recover: Option<u32>,
+    /// the oldest unacknowledged segment must be retransmitted without waiting for the timer
+    fast_rtx: bool,
+    /// ECN (RFC 3168) is used, before the handshake is complete: ECN is requested
+    ecn: bool,
+    /// we received a CE mark and set ECE until the DUT answers with CWR
+    ece_pending: bool,
+    /// we reduced the congestion window upon ECE and set CWR on the next new data
+    cwr_pending: bool,
+    /// seqn_nxt at the last reduction upon ECE, we react only once per window of data
+    ecn_recover: Option<u32>,
+    /// sent segments which are not yet acknowledged by the DUT
+    unacked: VecDeque<Segment>,
+    rtt: RttEstimator,
+    /// position of the retransmission timer in the timer wheel, None if not running,
+    /// while the send window is closed and no data is in flight, it is used as persist timer
+    rto_slot_and_index: Option<(u16, u16)>,
+    /// window probes sent since the DUT closed its window, each probe doubles the persist interval
+    persist_backoffs: u8,
+    /// number of payload segments of the DUT, which are acknowledged by one ACK, 1 acknowledges immediately
+    ack_frequency: u16,
+    /// maximum delay of an ACK in cycles
+    ack_delay: u64,
+    /// payload segments of the DUT, which are not yet acknowledged
+    acks_pending: u16,
+    /// position of the delayed ACK timer in the timer wheel, None if not running
+    dack_slot_and_index: Option<(u16, u16)>,
+    /// keepalive probes, which the DUT has not answered yet
+    keepalive_probes: u8,
+    /// MAC address of the DUT, if we are server, needed for retransmissions
+    pub peer_mac: MacAddress,
+    /// time stamp in cycles of the SYN, which started the connection
+    start_stamp: u64,
     /// either our IP, if we are client, or IP of DUT if we are server
     client_ip: u32,
     sent_payload_packets: u16,
@@ -47,12 +114,39 @@ impl Connection {
     #[inline]
     fn initialize(&mut self, client_sock: Option<(u32, u16)>, role: TcpRole) {
         self.seqn_nxt = 0;
-        //self.seqn_una = 0;
+        self.seqn_una = 0;
         self.ackn_nxt = 0;
+        self.irs = 0;
+        self.snd_wnd = 0;
+        self.snd_wl1 = 0;
+        self.snd_wl2 = 0;
+        self.wnd_blocked = false;
+        self.message.clear();
+        self.message_sent = 0;
+        self.time_wait = false;
+        self.options = NegotiatedOptions::new();
+        self.sack_blocks.clear();
+        self.reassembly.clear();
+        self.dup_acks = 0;
+        self.recover = None;
+        self.fast_rtx = false;
+        self.ecn = false;
+        self.ece_pending = false;
+        self.cwr_pending = false;
+        self.ecn_recover = None;
+        self.unacked.clear();
+        self.rto_slot_and_index = None;
+        self.persist_backoffs = 0;
+        self.ack_frequency = 1;
+        self.ack_delay = 0;
+        self.acks_pending = 0;
+        self.dack_slot_and_index = None;
+        self.keepalive_probes = 0;
         let s = client_sock.unwrap_or((0, 0));
         self.client_ip = s.0;
         self.client_port = s.1;
         self.wheel_slot_and_index = (0, 0);
+        self.start_stamp = 0;
         self.server_index = 0;
         self.sent_payload_packets = 0;
         self.recv_payload_packets = 0;
@@ -60,12 +154,18 @@ impl Connection {
     }
 
     #[inline]
-    fn initialize_with_details(&mut self, sock: Option<(u32, u16)>, role: TcpRole, store: Rc<RefCell<TEngineStore>>) {
+    fn initialize_with_details(
+        &mut self,
+        sock: Option<(u32, u16)>,
+        role: TcpRole,
+        store: Rc<RefCell<TEngineStore>>,
+        x_store: Rc<RefCell<ExtRecordStore>>,
+    ) {
         self.initialize(sock, role);
         if self.record.is_none() {
-            self.record = Some(Box::new(DetailedRecord::new(store)));
+            self.record = Some(Box::new(DetailedRecord::new(store, x_store)));
         } else {
-            self.record.as_mut().unwrap().re_new(store);
+            self.record.as_mut().unwrap().re_new(store, x_store);
         }
         self.record.as_mut().unwrap().initialize(sock, role);
     }
@@ -74,9 +174,40 @@ impl Connection {
     fn new() -> Connection {
         Connection {
             seqn_nxt: 0, //next seqn towards DUT
-            //seqn_una: 0, // acked by DUT
+            seqn_una: 0, // acked by DUT
             ackn_nxt: 0, //next ackn towards DUT
+            irs: 0,
+            snd_wnd: 0,
+            snd_wl1: 0,
+            snd_wl2: 0,
+            rcv_wnd: 0,
+            options: NegotiatedOptions::new(),
+            sack_blocks: SackBlocks::new(),
+            reassembly: ReassemblyQueue::new(),
+            wnd_blocked: false,
+            message: Vec::new(),
+            message_sent: 0,
+            time_wait: false,
+            cc: None,
+            dup_acks: 0,
+            recover: None,
+            fast_rtx: false,
+            ecn: false,
+            ece_pending: false,
+            cwr_pending: false,
+            ecn_recover: None,
+            unacked: VecDeque::new(),
+            rtt: RttEstimator::new(),
+            rto_slot_and_index: None,
+            persist_backoffs: 0,
+            ack_frequency: 1,
+            ack_delay: 0,
+            acks_pending: 0,
+            dack_slot_and_index: None,
+            keepalive_probes: 0,
+            peer_mac: MacAddress::nil(),
             wheel_slot_and_index: (0, 0),
+            start_stamp: 0,
             client_port: 0,
             client_ip: 0,
             server_index: 0,
@@ -156,6 +287,57 @@ impl Connection {
         }
     }
 
+    /// sets ReleaseCause::Timeout and records which timer fired
+    #[inline]
+    pub fn set_timeout(&mut self, cause: TimeoutCause) {
+        debug!("{:?} timeout in state {:?}", cause, self.state);
+        if self.record.is_some() {
+            self.record.as_mut().unwrap().set_release_cause(ReleaseCause::Timeout);
+            self.record.as_mut().unwrap().set_timeout_cause(cause);
+        }
+    }
+
+    /// reschedules the connection timeout, e.g. after a state transition or a segment of the DUT,
+    /// which also answers our keepalive probes
+    #[inline]
+    pub fn restart_timeout<T: Copy + PartialEq + fmt::Debug>(
+        &mut self,
+        wheel: &mut TimerWheel<T>,
+        after: u64,
+        key: T,
+        unused: T,
+    ) {
+        let old = wheel.replace(self.wheel_slot_and_index, unused);
+        assert_eq!(old.unwrap(), key);
+        self.wheel_slot_and_index = wheel.schedule(&after, key);
+        self.keepalive_probes = 0;
+    }
+
+    /// to be called when the connection timeout fired, returns true if a keepalive probe is due instead of the timeout
+    #[inline]
+    fn keepalive_due(&mut self, keepalive: &Option<Keepalive>) -> bool {
+        match *keepalive {
+            Some(ref keepalive) if self.state == TcpState::Established && !self.time_wait => {
+                if self.keepalive_probes < keepalive.probes {
+                    self.keepalive_probes += 1;
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// the timer which fired, when the connection timeout expired
+    #[inline]
+    fn timeout_cause(&self) -> TimeoutCause {
+        if self.keepalive_probes > 0 {
+            TimeoutCause::Keepalive
+        } else {
+            TimeoutCause::for_state(self.state)
+        }
+    }
+
     #[inline]
     pub fn inc_sent_payload_pkts(&mut self) -> usize {
         if self.record.is_some() {
@@ -184,10 +366,543 @@ impl Connection {
         self.recv_payload_packets as usize
     }
 
+    #[inline]
+    pub fn init_rtt(&mut self, rto_config: &RtoConfig) {
+        self.rtt.initialize(rto_config);
+    }
+
+    /// keeps a copy of a sent segment until it is acknowledged
+    #[inline]
+    pub fn push_unacked(&mut self, seqn: u32, flags: u8, payload: &[u8], now: u64) {
+        self.unacked.push_back(Segment::new(seqn, flags, payload, now));
+    }
+
+    #[inline]
+    pub fn has_unacked(&self) -> bool {
+        !self.unacked.is_empty()
+    }
+
+    /// the oldest unacknowledged segment, which is retransmitted when the timer expires
+    #[inline]
+    pub fn first_unacked_mut(&mut self) -> Option<&mut Segment> {
+        self.unacked.front_mut()
+    }
+
+    /// the oldest unacknowledged segment, which is not reported by a SACK block of the DUT,
+    /// falls back to the oldest one, if all segments are reported
+    pub fn first_unsacked_mut(&mut self) -> Option<&mut Segment> {
+        match self.unacked.iter().position(|segment| !segment.sacked) {
+            Some(i) => self.unacked.get_mut(i),
+            None => self.unacked.front_mut(),
+        }
+    }
+
+    /// marks the segments which are covered by the SACK blocks of the DUT, returns true if a segment is newly marked
+    pub fn sack_unacked(&mut self, sack_blocks: &SackBlocks) -> bool {
+        let mut marked = false;
+        for segment in self.unacked.iter_mut().filter(|segment| !segment.sacked) {
+            if sack_blocks.covers(segment.seqn, segment.end_seqn()) {
+                segment.sacked = true;
+                marked = true;
+            }
+        }
+        marked
+    }
+
+    /// forgets the SACK information of the DUT, which may have discarded the reported data (RFC 2018, 8)
+    pub fn clear_sacked(&mut self) {
+        for segment in self.unacked.iter_mut() {
+            segment.sacked = false;
+        }
+    }
+
+    /// removes acknowledged segments from the retransmission queue and takes an RTT sample,
+    /// returns true if new data is acknowledged
+    pub fn ack_unacked(&mut self, ackn: u32, now: u64, rto_config: &RtoConfig) -> bool {
+        if !seq_gt(ackn, self.seqn_una) || !seq_leq(ackn, self.seqn_nxt) {
+            return false;
+        }
+        self.seqn_una = ackn;
+        let mut rtt_sample = None;
+        while let Some(mut segment) = self.unacked.pop_front() {
+            if seq_leq(segment.end_seqn(), ackn) {
+                if !segment.retransmitted {
+                    rtt_sample = Some(now.wrapping_sub(segment.stamp));
+                }
+            } else {
+                if seq_lt(segment.seqn, ackn) {
+                    // partially acknowledged, keep only the unacknowledged part
+                    let mut acked = ackn.wrapping_sub(segment.seqn) as usize;
+                    if segment.flags & SEG_SYN != 0 {
+                        // data which was sent with the SYN, e.g. with TCP Fast Open, is resent with an ACK
+                        segment.flags = segment.flags & !SEG_SYN | SEG_ACK;
+                        acked -= 1;
+                    }
+                    segment.payload.drain(..acked);
+                    segment.seqn = ackn;
+                }
+                self.unacked.push_front(segment);
+                break;
+            }
+        }
+        if let Some(r) = rtt_sample {
+            self.rtt.sample(r, rto_config);
+        }
+        self.rtt.reset_backoffs();
+        true
+    }
+
+    /// takes the send window from the SYN or SYN-ACK of the DUT
+    #[inline]
+    pub fn init_snd_wnd(&mut self, seqn: u32, ackn: u32, wnd: u32) {
+        self.snd_wnd = wnd;
+        self.snd_wl1 = seqn;
+        self.snd_wl2 = ackn;
+    }
+
+    /// updates the send window, unless the segment is older than the one of the last update (RFC 793, p. 72),
+    /// returns true if the DUT closed the window with this segment
+    pub fn update_snd_wnd(&mut self, seqn: u32, ackn: u32, wnd: u32) -> bool {
+        if !seq_leq(self.seqn_una, ackn) || !seq_leq(ackn, self.seqn_nxt) {
+            return false;
+        }
+        if seq_lt(self.snd_wl1, seqn) || self.snd_wl1 == seqn && seq_leq(self.snd_wl2, ackn) {
+            let closed = wnd == 0 && self.snd_wnd > 0;
+            self.snd_wnd = wnd;
+            self.snd_wl1 = seqn;
+            self.snd_wl2 = ackn;
+            if wnd > 0 {
+                self.persist_backoffs = 0;
+            }
+            return closed;
+        }
+        false
+    }
+
+    /// the window field of our segments, scaled if window scaling is negotiated
+    #[inline]
+    pub fn window_field(&self) -> u16 {
+        cmp::min(self.rcv_wnd >> self.options.rcv_wscale, 0xFFFF) as u16
+    }
+
+    /// the window field of our SYN or SYN-ACK, which is never scaled (RFC 7323, 2.2)
+    #[inline]
+    pub fn syn_window_field(&self) -> u16 {
+        cmp::min(self.rcv_wnd, 0xFFFF) as u16
+    }
+
+    /// the window in bytes from the window field of a segment of the DUT, which is not a SYN
+    #[inline]
+    pub fn scaled_window(&self, window_field: u16) -> u32 {
+        (window_field as u32) << self.options.snd_wscale
+    }
+
+    /// number of bytes sent, but not yet acknowledged
+    #[inline]
+    pub fn in_flight(&self) -> u32 {
+        self.seqn_nxt.wrapping_sub(self.seqn_una)
+    }
+
+    /// number of bytes we may send before we exhaust the send or the congestion window
+    #[inline]
+    pub fn usable_window(&self) -> u32 {
+        match self.cc {
+            Some(ref cc) => cmp::min(self.snd_wnd, cc.cwnd()).saturating_sub(self.in_flight()),
+            None => self.snd_wnd.saturating_sub(self.in_flight()),
+        }
+    }
+
+    #[inline]
+    pub fn set_wnd_blocked(&mut self, blocked: bool) {
+        self.wnd_blocked = blocked;
+    }
+
+    #[inline]
+    pub fn wnd_blocked(&self) -> bool {
+        self.wnd_blocked
+    }
+
+    #[inline]
+    pub fn set_start_stamp(&mut self, stamp: u64) {
+        self.start_stamp = stamp;
+    }
+
+    #[inline]
+    pub fn start_stamp(&self) -> u64 {
+        self.start_stamp
+    }
+
+    /// queues an application message, which is sent in segments of at most one MSS,
+    /// a message which is queued while another is pending is appended to it
+    pub fn send_message(&mut self, message: &[u8]) {
+        if !self.message_pending() {
+            self.message.clear();
+            self.message_sent = 0;
+        }
+        self.message.extend_from_slice(message);
+    }
+
+    #[inline]
+    pub fn message_pending(&self) -> bool {
+        self.message_sent < self.message.len()
+    }
+
+    /// the next segment of the pending message with at most max_len bytes, and whether it is the last segment
+    #[inline]
+    pub fn message_segment(&self, max_len: usize) -> (&[u8], bool) {
+        let end = cmp::min(self.message_sent + max_len, self.message.len());
+        (&self.message[self.message_sent..end], end == self.message.len())
+    }
+
+    #[inline]
+    pub fn message_segment_sent(&mut self, len: usize) {
+        self.message_sent += len;
+    }
+
+    /// the maximum payload of our segments, the MSS of the DUT less the size of our options (RFC 6691)
+    #[inline]
+    pub fn max_segment_size(&self) -> usize {
+        (self.options.mss as usize).saturating_sub(OptionBuf::options(&self.options, &self.sack_blocks).as_slice().len())
+    }
+
+    /// sets up congestion control for a new connection, reuses the existing controller if possible
+    pub fn set_congestion_control(&mut self, algorithm: CongestionControlType, mss: u32, cpu_clock: u64) {
+        let reuse = match self.cc {
+            Some(ref cc) => cc.algorithm() == algorithm,
+            None => false,
+        };
+        if reuse {
+            self.cc.as_mut().unwrap().init(mss);
+        } else {
+            self.cc = Some(new_congestion_control(algorithm, mss, cpu_clock));
+        }
+    }
+
+    /// enables delayed ACKs, delay is in cycles
+    #[inline]
+    pub fn set_delayed_ack(&mut self, frequency: u16, delay: u64) {
+        self.ack_frequency = cmp::max(frequency, 1);
+        self.ack_delay = delay;
+    }
+
+    /// to be called for in-order payload of the DUT, which we do not answer with data, returns true if the ACK
+    /// is delayed, otherwise a pure ACK must be sent now, a running delayed ACK timer is not restarted
+    #[inline]
+    pub fn delay_ack<T: Copy>(&mut self, wheel: &mut TimerWheel<T>, key: T) -> bool {
+        self.acks_pending += 1;
+        if self.acks_pending >= self.ack_frequency {
+            return false;
+        }
+        if self.dack_slot_and_index.is_none() {
+            self.dack_slot_and_index = Some(wheel.schedule(&self.ack_delay, key));
+        }
+        true
+    }
+
+    /// to be called for each segment which carries our ackn_nxt, i.e. pending ACKs are piggybacked
+    #[inline]
+    pub fn ack_sent(&mut self) {
+        self.acks_pending = 0;
+    }
+
+    #[inline]
+    pub fn acks_pending(&self) -> bool {
+        self.acks_pending > 0
+    }
+
+    /// to be called when the timer wheel has fired the delayed ACK timer
+    #[inline]
+    fn dack_expired(&mut self) {
+        self.dack_slot_and_index = None;
+    }
+
+    /// to be called when the MSS of the DUT is known, restarts congestion control with the new MSS
+    #[inline]
+    pub fn set_mss(&mut self, mss: u16) {
+        self.options.mss = mss;
+        if let Some(ref mut cc) = self.cc {
+            cc.init(mss as u32);
+        }
+    }
+
+    #[inline]
+    pub fn cwnd(&self) -> Option<u32> {
+        self.cc.as_ref().map(|cc| cc.cwnd())
+    }
+
+    #[inline]
+    pub fn ssthresh(&self) -> Option<u32> {
+        self.cc.as_ref().map(|cc| cc.ssthresh())
+    }
+
+    /// to be called when acked bytes of new data were acknowledged,
+    /// returns true if a partial ACK in fast recovery requires the retransmission of the next segment
+    pub fn congestion_ack(&mut self, acked: u32, now: u64) -> bool {
+        self.dup_acks = 0;
+        if let Some(recover) = self.recover {
+            if seq_lt(self.seqn_una, recover) {
+                self.fast_rtx = self.has_unacked();
+                return self.fast_rtx;
+            }
+            // full ACK, cwnd was already set to ssthresh when we entered fast recovery
+            self.recover = None;
+            return false;
+        }
+        if let Some(ref mut cc) = self.cc {
+            cc.on_ack(acked, now);
+        }
+        false
+    }
+
+    /// counts a duplicate ACK, returns true if we enter fast recovery and must retransmit
+    pub fn congestion_dup_ack(&mut self, now: u64) -> bool {
+        self.dup_acks = self.dup_acks.saturating_add(1);
+        if self.dup_acks == DUP_ACK_THRESHOLD && self.recover.is_none() && self.has_unacked() {
+            let in_flight = self.in_flight();
+            if let Some(ref mut cc) = self.cc {
+                cc.on_congestion(in_flight, now);
+                self.recover = Some(self.seqn_nxt);
+                self.fast_rtx = true;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// to be called when the retransmission timer fired for the first time for the oldest segment
+    pub fn congestion_timeout(&mut self, now: u64) {
+        self.dup_acks = 0;
+        self.recover = None;
+        let in_flight = self.in_flight();
+        if let Some(ref mut cc) = self.cc {
+            cc.on_timeout(in_flight, now);
+        }
+    }
+
+    #[inline]
+    pub fn fast_rtx(&self) -> bool {
+        self.fast_rtx
+    }
+
+    /// requests ECN with our SYN, or, after the handshake, sets whether ECN is used
+    #[inline]
+    pub fn set_ecn(&mut self, ecn: bool) {
+        self.ecn = ecn;
+        if self.record.is_some() {
+            self.record.as_mut().unwrap().set_ecn(ecn);
+        }
+    }
+
+    #[inline]
+    pub fn ecn(&self) -> bool {
+        self.ecn
+    }
+
+    /// to be called for a segment of the DUT with a CE mark
+    #[inline]
+    pub fn ce_received(&mut self) {
+        self.ece_pending = true;
+        if self.record.is_some() {
+            self.record.as_mut().unwrap().inc_ce_marks();
+        }
+    }
+
+    /// to be called for a segment of the DUT with CWR
+    #[inline]
+    pub fn cwr_received(&mut self) {
+        self.ece_pending = false;
+    }
+
+    /// true, if our ACKs must carry ECE
+    #[inline]
+    pub fn ece_pending(&self) -> bool {
+        self.ece_pending
+    }
+
+    /// to be called for an ACK of the DUT with ECE, returns true if we reduce the congestion window,
+    /// which happens at most once per window of data (RFC 3168, 6.1.2)
+    pub fn ece_received(&mut self, now: u64) -> bool {
+        if let Some(recover) = self.ecn_recover {
+            if seq_lt(self.seqn_una, recover) {
+                return false;
+            }
+        }
+        self.ecn_recover = Some(self.seqn_nxt);
+        self.cwr_pending = true;
+        let in_flight = self.in_flight();
+        if let Some(ref mut cc) = self.cc {
+            cc.on_congestion(in_flight, now);
+        }
+        if self.record.is_some() {
+            self.record.as_mut().unwrap().inc_ecn_reductions();
+        }
+        true
+    }
+
+    /// returns true, if the next new data segment must carry CWR
+    #[inline]
+    pub fn take_cwr(&mut self) -> bool {
+        mem::replace(&mut self.cwr_pending, false)
+    }
+
+    /// requests the retransmission of the oldest segment without waiting for the retransmission timer
+    #[inline]
+    pub fn request_fast_rtx(&mut self) {
+        self.fast_rtx = self.has_unacked();
+    }
+
+    /// returns true, if a fast retransmission was pending
+    #[inline]
+    pub fn take_fast_rtx(&mut self) -> bool {
+        mem::replace(&mut self.fast_rtx, false)
+    }
+
+    #[inline]
+    pub fn rto(&self) -> u64 {
+        self.rtt.rto()
+    }
+
+    #[inline]
+    pub fn srtt(&self) -> u64 {
+        self.rtt.srtt()
+    }
+
+    #[inline]
+    pub fn rto_backoffs(&self) -> u8 {
+        self.rtt.backoffs()
+    }
+
+    /// doubles the RTO, from the second consecutive timeout on the SACK information of the DUT is no longer trusted
+    #[inline]
+    pub fn backoff_rto(&mut self, rto_config: &RtoConfig) -> u8 {
+        let backoffs = self.rtt.backoff(rto_config);
+        if backoffs > 1 {
+            self.clear_sacked();
+        }
+        backoffs
+    }
+
+    #[inline]
+    pub fn rto_running(&self) -> bool {
+        self.rto_slot_and_index.is_some()
+    }
+
+    /// starts the retransmission timer, if it is not already running
+    #[inline]
+    pub fn start_rto<T: Copy>(&mut self, wheel: &mut TimerWheel<T>, key: T) {
+        if self.rto_slot_and_index.is_none() {
+            self.rto_slot_and_index = Some(wheel.schedule(&self.rtt.rto(), key));
+        }
+    }
+
+    /// stops the retransmission timer by overwriting its wheel entry with the unused key
+    #[inline]
+    pub fn stop_rto<T: Copy + PartialEq + fmt::Debug>(&mut self, wheel: &mut TimerWheel<T>, unused: T) {
+        if let Some(slot_and_index) = self.rto_slot_and_index.take() {
+            let old = wheel.replace(slot_and_index, unused);
+            assert_ne!(old.unwrap(), unused);
+        }
+    }
+
+    #[inline]
+    pub fn restart_rto<T: Copy + PartialEq + fmt::Debug>(&mut self, wheel: &mut TimerWheel<T>, key: T, unused: T) {
+        self.stop_rto(wheel, unused);
+        self.start_rto(wheel, key);
+    }
+
+    /// to be called when the timer wheel has fired the retransmission timer
+    #[inline]
+    fn rto_expired(&mut self) {
+        self.rto_slot_and_index = None;
+    }
+
+    /// a segment is held back by a closed send window, while no data is in flight, which could
+    /// trigger a window update of the DUT, the persist timer must run (RFC 1122, 4.2.2.17)
+    #[inline]
+    pub fn persist_due(&self) -> bool {
+        self.wnd_blocked && self.snd_wnd == 0 && !self.has_unacked() && !self.rto_running()
+    }
+
+    /// starts the persist timer with the RTO, doubled for each window probe which we have already sent
+    #[inline]
+    pub fn start_persist<T: Copy>(&mut self, wheel: &mut TimerWheel<T>, key: T, rto_config: &RtoConfig) {
+        if self.rto_slot_and_index.is_none() {
+            let interval = cmp::min(self.rtt.rto() << cmp::min(self.persist_backoffs, 16), rto_config.max);
+            self.rto_slot_and_index = Some(wheel.schedule(&interval, key));
+        }
+    }
+
+    /// stops the persist timer, once the DUT opened its window
+    #[inline]
+    pub fn stop_persist<T: Copy + PartialEq + fmt::Debug>(&mut self, wheel: &mut TimerWheel<T>, unused: T) {
+        if self.snd_wnd > 0 && !self.has_unacked() {
+            self.stop_rto(wheel, unused);
+        }
+    }
+
+    /// to be called for each window probe
+    #[inline]
+    pub fn inc_persist_backoffs(&mut self) {
+        self.persist_backoffs = self.persist_backoffs.saturating_add(1);
+    }
+
+    /// queues a copy of out-of-order payload of the DUT, returns false if it is not queued
+    #[inline]
+    pub fn queue_out_of_order(&mut self, seqn: u32, payload: &[u8]) -> bool {
+        self.reassembly.insert(seqn, payload, self.ackn_nxt, self.rcv_wnd)
+    }
+
+    /// to be called after in-order payload advanced ackn_nxt, queued segments which continue the in-order data
+    /// are delivered, see pop_delivered, returns the number of delivered segments
+    pub fn reassemble(&mut self) -> usize {
+        if self.reassembly.is_empty() {
+            return 0;
+        }
+        let (ackn, delivered) = self.reassembly.deliver(self.ackn_nxt);
+        self.ackn_nxt = ackn;
+        self.sack_blocks.remove_acked(ackn);
+        delivered
+    }
+
+    /// the payload of the next reassembled segment, which is not yet handed to the payload logic
+    #[inline]
+    pub fn pop_delivered(&mut self) -> Option<Vec<u8>> {
+        self.reassembly.pop_delivered()
+    }
+
+    #[inline]
+    pub fn has_delivered(&self) -> bool {
+        self.reassembly.has_delivered()
+    }
+
+    /// netfcts has no TIME_WAIT state, the connection is Closed and the port is held back by the connection manager
+    #[inline]
+    pub fn in_time_wait(&self) -> bool {
+        self.time_wait
+    }
+
+    /// the RST which aborts the connection, None if the DUT has no state which must be reset
+    pub fn reset(&self) -> Option<Reset> {
+        match self.state {
+            TcpState::Listen | TcpState::Closed => None,
+            state => Some(Reset {
+                sock: (self.client_ip, self.client_port),
+                server_index: self.server_index(),
+                peer_mac: self.peer_mac,
+                seqn: self.seqn_nxt,
+                ackn: if state == TcpState::SynSent { None } else { Some(self.ackn_nxt) },
+            }),
+        }
+    }
+
     #[inline]
     fn release(&mut self) {
         self.client_port = 0;
+        self.unacked.clear();
         if self.record.is_some() {
+            if let Some(ref cc) = self.cc {
+                self.record.as_mut().unwrap().set_congestion(cc.cwnd(), cc.ssthresh());
+            }
             self.record.as_mut().unwrap().release();
         }
     }
@@ -210,15 +925,113 @@ impl Connection {
     }
 }
 
-impl<'a> Clone for Connection {
-    fn clone(&self) -> Self {
-        Connection::new()
+/// what we need to know for aborting a connection with a RST, after the connection is released
+#[derive(Debug, Clone, Copy)]
+pub struct Reset {
+    /// for the client our socket, for the server the socket of the DUT
+    pub sock: (u32, u16),
+    pub server_index: usize,
+    pub peer_mac: MacAddress,
+    pub seqn: u32,
+    /// None, if we have not yet seen a segment of the DUT
+    pub ackn: Option<u32>,
+}
+
+impl<'a> Clone for Connection {
+    fn clone(&self) -> Self {
+        Connection::new()
+    }
+}
+
+/// keepalive probes, times in cycles, the idle time is the timeout of ESTABLISHED
+#[derive(Debug, Clone, Copy)]
+pub struct Keepalive {
+    pub interval: u64,
+    pub probes: u8,
+}
+
+/// the timer which fired, if a connection is released with ReleaseCause::Timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutCause {
+    SynSent,
+    SynReceived,
+    /// idle timeout in ESTABLISHED
+    Established,
+    /// one of the closing states
+    FinWait,
+    /// maximum number of retransmissions exceeded
+    Retransmission,
+    /// the DUT did not answer our keepalive probes
+    Keepalive,
+}
+
+impl TimeoutCause {
+    pub fn for_state(state: TcpState) -> TimeoutCause {
+        match state {
+            TcpState::SynSent => TimeoutCause::SynSent,
+            TcpState::SynReceived | TcpState::Listen => TimeoutCause::SynReceived,
+            TcpState::Established => TimeoutCause::Established,
+            _ => TimeoutCause::FinWait,
+        }
+    }
+}
+
+/// connection data, which is not covered by the ConRecord, it is stored under the index of the ConRecord
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExtRecord {
+    /// congestion window and slow start threshold when the connection was released
+    pub cwnd: u32,
+    pub ssthresh: u32,
+    pub timeout: Option<TimeoutCause>,
+    /// ECN was negotiated in the handshake
+    pub ecn: bool,
+    /// segments of the DUT with a CE mark
+    pub ce_marks: u32,
+    /// reductions of the congestion window upon ECE of the DUT
+    pub ecn_reductions: u32,
+}
+
+impl fmt::Display for ExtRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cwnd= {}, ssthresh= {}", self.cwnd, self.ssthresh)?;
+        if let Some(timeout) = self.timeout {
+            write!(f, ", timeout= {:?}", timeout)?;
+        }
+        if self.ecn {
+            write!(f, ", ecn: ce_marks= {}, reductions= {}", self.ce_marks, self.ecn_reductions)?;
+        }
+        Ok(())
+    }
+}
+
+pub type ExtRecordStore = Vec<ExtRecord>;
+
+/// the records of a connection manager, which a pipeline delivers with MessageFrom::CRecords,
+/// ext_records[i] extends the i-th ConRecord
+pub struct EngineRecords {
+    pub con_records: RecordStore<ConRecord>,
+    pub ext_records: ExtRecordStore,
+}
+
+impl EngineRecords {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.con_records.len()
+    }
+
+    /// the ConRecords together with their extensions
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a ConRecord, Option<&'a ExtRecord>)> + 'a {
+        self.con_records
+            .iter()
+            .enumerate()
+            .map(move |(i, c)| (c, self.ext_records.get(i)))
     }
 }
 
 pub struct DetailedRecord {
     con_rec: Option<usize>,
     store: Option<Rc<RefCell<TEngineStore>>>,
+    x_store: Option<Rc<RefCell<ExtRecordStore>>>,
 }
 
 
@@ -228,18 +1041,65 @@ impl DetailedRecord {
         self.store().borrow_mut().get_mut(self.con_rec()).init(role, 0, client_sock);
     }
 
-    fn new(store: Rc<RefCell<TEngineStore>>) -> DetailedRecord {
+    fn new(store: Rc<RefCell<TEngineStore>>, x_store: Rc<RefCell<ExtRecordStore>>) -> DetailedRecord {
         let con_rec = store.borrow_mut().get_next_slot();
+        DetailedRecord::init_ext_record(&x_store, con_rec);
         DetailedRecord {
             con_rec: Some(con_rec),
             store: Some(store),
+            x_store: Some(x_store),
         }
     }
 
-    fn re_new(&mut self, store: Rc<RefCell<TEngineStore>>) {
+    fn re_new(&mut self, store: Rc<RefCell<TEngineStore>>, x_store: Rc<RefCell<ExtRecordStore>>) {
         let con_rec = store.borrow_mut().get_next_slot();
+        DetailedRecord::init_ext_record(&x_store, con_rec);
         self.con_rec = Some(con_rec);
         self.store = Some(store);
+        self.x_store = Some(x_store);
+    }
+
+    fn init_ext_record(x_store: &Rc<RefCell<ExtRecordStore>>, con_rec: usize) {
+        let mut x_store = x_store.borrow_mut();
+        if x_store.len() <= con_rec {
+            x_store.resize(con_rec + 1, ExtRecord::default());
+        } else {
+            x_store[con_rec] = ExtRecord::default();
+        }
+    }
+
+    #[inline]
+    fn update_ext_record<F: FnOnce(&mut ExtRecord)>(&mut self, f: F) {
+        let con_rec = self.con_rec();
+        f(&mut self.x_store.as_ref().unwrap().borrow_mut()[con_rec]);
+    }
+
+    #[inline]
+    pub fn set_congestion(&mut self, cwnd: u32, ssthresh: u32) {
+        self.update_ext_record(|x| {
+            x.cwnd = cwnd;
+            x.ssthresh = ssthresh;
+        });
+    }
+
+    #[inline]
+    pub fn set_timeout_cause(&mut self, cause: TimeoutCause) {
+        self.update_ext_record(|x| x.timeout = Some(cause));
+    }
+
+    #[inline]
+    pub fn set_ecn(&mut self, ecn: bool) {
+        self.update_ext_record(|x| x.ecn = ecn);
+    }
+
+    #[inline]
+    pub fn inc_ce_marks(&mut self) {
+        self.update_ext_record(|x| x.ce_marks += 1);
+    }
+
+    #[inline]
+    pub fn inc_ecn_reductions(&mut self) {
+        self.update_ext_record(|x| x.ecn_reductions += 1);
     }
 
     #[inline]
@@ -252,6 +1112,7 @@ impl DetailedRecord {
         //trace!("releasing con record on port {}", self.port());
         self.con_rec = None;
         self.store = None;
+        self.x_store = None;
     }
 }
 
@@ -290,12 +1151,33 @@ pub static GLOBAL_MANAGER_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 pub struct ConnectionManagerC {
     c_record_store: Option<Rc<RefCell<RecordStore<ConRecord>>>>,
-    free_ports: VecDeque<u16>,
-    ready: VecDeque<u16>,
+    x_record_store: Option<Rc<RefCell<ExtRecordStore>>>,
+    /// the shuffled port range of this pipeline
+    ports: Vec<u16>,
+    /// number of sockets which were taken from the pool for the first time, see fresh_sock
+    fresh_socks: usize,
+    /// sockets of released connections, they are reused when all sockets of the pool have been used once
+    free_socks: VecDeque<(u32, u16)>,
+    /// the slot in connections of each socket in use
+    sock2slot: HashMap<(u32, u16), usize>,
+    /// connections are allocated when the number of concurrent connections grows, released ones are reused
+    connections: Vec<Connection>,
+    free_slots: Vec<usize>,
+    ready: VecDeque<(u32, u16)>,
+    /// sockets of connections with an expired retransmission timer
+    rtx_ready: VecDeque<(u32, u16)>,
+    /// timed out connections, which still must be reset
+    rst_ready: VecDeque<Reset>,
+    /// sockets of connections with an expired delayed ACK timer
+    ack_ready: VecDeque<(u32, u16)>,
+    /// sockets of idle connections, which must send a keepalive probe
+    keepalive_ready: VecDeque<(u32, u16)>,
+    /// sockets of connections with an expired persist timer, which must send a window probe
+    persist_ready: VecDeque<(u32, u16)>,
     /// min number of free ports
     min_free_ports: usize,
-    // ports of connections with data to send and in state Established when enqueued
-    port2con: Vec<Connection>,
+    /// number of ports, which are held back in TIME_WAIT
+    time_wait_ports: usize,
     pci: PortQueue,
     // the PortQueue for which connections are managed
     pipeline_id: PipelineId,
@@ -303,8 +1185,12 @@ pub struct ConnectionManagerC {
     available_ports_count: usize,
     // e.g. used as a listen port, not assigned by create
     listen_port: u16,
+    /// ip address of this pipeline, without a client pool also the source address of the client connections
     ip: u32,
-    // ip address to use for connections of this manager
+    /// first client source address, with a client pool the first host of the pool, otherwise ip
+    pool_base: u32,
+    /// number of client source addresses
+    pool_size: u32,
     /// with or without recording of connections
     detailed_records: bool,
 }
@@ -312,46 +1198,110 @@ pub struct ConnectionManagerC {
 const MAX_CONNECTIONS: usize = 0xFFFF as usize;
 const MAX_RECORDS: usize = 0x3FFFF as usize;
 
+/// pops sockets from a ready queue, until pred holds for one of them, which is returned,
+/// the connection of a queued socket may have been released or served in the meantime
+#[inline]
+fn pop_ready<P: FnMut(&(u32, u16)) -> bool>(queue: &mut VecDeque<(u32, u16)>, mut pred: P) -> Option<(u32, u16)> {
+    while let Some(sock) = queue.pop_front() {
+        if pred(&sock) {
+            return Some(sock);
+        }
+    }
+    None
+}
+
+/// calls f for each socket, whose timer expired until now, released timers are skipped
+#[inline]
+fn drain_wheel<F: FnMut((u32, u16))>(wheel: &mut TimerWheel<(u32, u16)>, now: &u64, mut f: F) {
+    loop {
+        let (drain, more) = wheel.tick(now);
+        if let Some(drain) = drain {
+            for sock in drain {
+                if sock != (0, 0) {
+                    f(sock);
+                }
+            }
+        }
+        if !more {
+            break;
+        }
+    }
+}
+
 impl ConnectionManagerC {
-    pub fn new(pipeline_id: PipelineId, pci: PortQueue, l4flow: &L4Flow, detailed_records: bool) -> ConnectionManagerC {
+    /// the client connections use the source addresses of client_pool, if any, together with the port range
+    /// of this pipeline, i.e. all pipelines of a port may share the same pool
+    pub fn new(
+        pipeline_id: PipelineId,
+        pci: PortQueue,
+        l4flow: &L4Flow,
+        client_pool: Option<Ipv4Net>,
+        detailed_records: bool,
+    ) -> ConnectionManagerC {
         let old_manager_count: u16 = GLOBAL_MANAGER_COUNT.fetch_add(1, Ordering::SeqCst) as u16;
         let (ip, tcp_port_base) = (l4flow.ip, l4flow.port);
         let port_mask = pci.port.get_tcp_dst_port_mask();
         let max_tcp_port: u16 = tcp_port_base + !port_mask;
+        let (pool_base, pool_size) = match client_pool {
+            Some(net) => {
+                // the network and broadcast addresses are excluded, except for /31 and /32 (RFC 3021)
+                let size = 1u64 << (32 - net.prefix_len());
+                let hosts = if net.prefix_len() >= 31 { size } else { size - 2 };
+                (u32::from(net.hosts().next().unwrap()), hosts as u32)
+            }
+            None => (ip, 1),
+        };
         let store = if detailed_records {
             Some(Rc::new(RefCell::new(RecordStore::with_capacity(MAX_RECORDS))))
         } else {
             None
         };
-        let avail_ports;
+        let x_store = if detailed_records {
+            Some(Rc::new(RefCell::new(ExtRecordStore::with_capacity(MAX_RECORDS))))
+        } else {
+            None
+        };
+        // port 0 is reserved and not usable for us, ports are shuffled for better load sharing in DUTs
+        // max_tcp_port itself is reserved for the server side for listening
+        let ports = shuffle_ports(if tcp_port_base == 0 { 1 } else { tcp_port_base }, max_tcp_port - 1);
+        //let ports = (if tcp_port_base == 0 { 1 } else { tcp_port_base }.. max_tcp_port - 1).collect();
+        let avail_ports = ports.len() * pool_size as usize;
         let cm = ConnectionManagerC {
             c_record_store: store,
-            port2con: vec![Connection::new(); (!port_mask + 1) as usize],
-            // port 0 is reserved and not usable for us, ports are shuffled for better load sharing in DUTs
-            // max_tcp_port itself is reserved for the server side for listening
-            free_ports: {
-                let vec = shuffle_ports(if tcp_port_base == 0 { 1 } else { tcp_port_base }, max_tcp_port - 1);
-                //let vec = (if tcp_port_base == 0 { 1 } else { tcp_port_base }.. max_tcp_port - 1).collect();
-                avail_ports = vec.len();
-                VecDeque::<u16>::from(vec)
-            },
+            x_record_store: x_store,
+            ports,
+            fresh_socks: 0,
+            free_socks: VecDeque::new(),
+            sock2slot: HashMap::new(),
+            connections: Vec::new(),
+            free_slots: Vec::new(),
             available_ports_count: avail_ports,
             ready: VecDeque::with_capacity(MAX_CONNECTIONS), // connections which became Established (but may not longer be)
-            min_free_ports: !port_mask as usize + 1,
+            rtx_ready: VecDeque::with_capacity(MAX_CONNECTIONS),
+            rst_ready: VecDeque::with_capacity(MAX_CONNECTIONS),
+            ack_ready: VecDeque::with_capacity(MAX_CONNECTIONS),
+            keepalive_ready: VecDeque::with_capacity(MAX_CONNECTIONS),
+            persist_ready: VecDeque::with_capacity(MAX_CONNECTIONS),
+            min_free_ports: avail_ports,
+            time_wait_ports: 0,
             pci,
             pipeline_id,
             tcp_port_base,
             listen_port: max_tcp_port,
             ip,
+            pool_base,
+            pool_size,
             detailed_records,
         };
         // we use the port max_tcp_port for returning traffic to us, do not add it to free_ports
         info!(
-            "created ConnectionManager {} for port {}, rxq {}, ip= {}, tcp ports {} - {}",
+            "created ConnectionManager {} for port {}, rxq {}, ip= {}, client ips {} - {}, tcp ports {} - {}",
             old_manager_count,
             cm.pci.port_id(),
             cm.pci.rxq(),
             Ipv4Addr::from(ip),
+            Ipv4Addr::from(pool_base),
+            Ipv4Addr::from(pool_base + (pool_size - 1)),
             if tcp_port_base == 0 { 1 } else { tcp_port_base },
             max_tcp_port - 1,
         );
@@ -366,7 +1316,23 @@ impl ConnectionManagerC {
 
     #[inline]
     pub fn concurrent_connections(&self) -> usize {
-        self.available_ports_count - self.free_ports.len()
+        self.available_ports_count - self.free_ports() - self.time_wait_ports
+    }
+
+    #[inline]
+    fn free_ports(&self) -> usize {
+        self.available_ports_count - self.fresh_socks + self.free_socks.len()
+    }
+
+    /// false while all ports are open or held back in TIME_WAIT
+    #[inline]
+    pub fn has_free_ports(&self) -> bool {
+        self.free_ports() > 0
+    }
+
+    #[inline]
+    pub fn time_wait_connections(&self) -> usize {
+        self.time_wait_ports
     }
 
     #[inline]
@@ -375,28 +1341,69 @@ impl ConnectionManagerC {
     }
 
 
+    /// the i-th socket of the pool, the source addresses take turns
+    #[inline]
+    fn fresh_sock(&self, i: usize) -> (u32, u16) {
+        let pool_size = self.pool_size as usize;
+        (self.pool_base + (i % pool_size) as u32, self.ports[i / pool_size])
+    }
+
+    /// the sockets of the pool are used once, before released sockets are reused
+    #[inline]
+    fn pop_free_sock(&mut self) -> Option<(u32, u16)> {
+        if self.fresh_socks < self.available_ports_count {
+            let sock = self.fresh_sock(self.fresh_socks);
+            self.fresh_socks += 1;
+            Some(sock)
+        } else {
+            self.free_socks.pop_front()
+        }
+    }
+
+    /// the connection of a socket in use
+    #[inline]
+    fn get_mut_con(&mut self, sock: &(u32, u16)) -> Option<&mut Connection> {
+        match self.sock2slot.get(sock) {
+            Some(&slot) => Some(&mut self.connections[slot]),
+            None => None,
+        }
+    }
+
+    /// returns the socket to the free sockets and the connection to the free slots
     #[inline]
-    fn get_mut_con(&mut self, p: &u16) -> &mut Connection {
-        &mut self.port2con[(p - self.tcp_port_base) as usize]
+    fn free(&mut self, sock: &(u32, u16)) {
+        if let Some(slot) = self.sock2slot.remove(sock) {
+            self.free_slots.push(slot);
+            self.free_socks.push_back(*sock);
+        }
     }
 
     // create a new connection, if out of resources return None
     #[inline]
     pub fn create(&mut self, role: TcpRole) -> Option<&mut Connection> {
-        let opt_port = self.free_ports.pop_front();
-        if opt_port.is_some() {
-            let port = opt_port.unwrap();
+        let opt_sock = self.pop_free_sock();
+        if opt_sock.is_some() {
+            let sock = opt_sock.unwrap();
+            let slot = match self.free_slots.pop() {
+                Some(slot) => slot,
+                None => {
+                    self.connections.push(Connection::new());
+                    self.connections.len() - 1
+                }
+            };
+            self.sock2slot.insert(sock, slot);
             {
-                let sock = (self.ip, port);
+                let c = &mut self.connections[slot];
                 if self.detailed_records {
                     let store = Rc::clone(self.c_record_store.as_ref().unwrap());
-                    self.get_mut_con(&port).initialize_with_details(Some(sock), role, store);
+                    let x_store = Rc::clone(self.x_record_store.as_ref().unwrap());
+                    c.initialize_with_details(Some(sock), role, store, x_store);
                 } else {
-                    self.get_mut_con(&port).initialize(Some(sock), role);
+                    c.initialize(Some(sock), role);
                 }
             }
-            self.min_free_ports = cmp::min(self.min_free_ports, self.free_ports.len());
-            Some(self.get_mut_con(&port))
+            self.min_free_ports = cmp::min(self.min_free_ports, self.free_ports());
+            Some(&mut self.connections[slot])
         } else {
             warn!("out of ports");
             None
@@ -418,86 +1425,319 @@ impl ConnectionManagerC {
         self.ip
     }
 
+    /// true for the client source addresses
+    #[inline]
+    pub fn owns_ip(&self, ip: u32) -> bool {
+        ip.wrapping_sub(self.pool_base) < self.pool_size
+    }
+
     #[inline]
     pub fn listen_port(&self) -> u16 {
         self.listen_port
     }
 
     #[inline]
-    pub fn get_mut_by_port(&mut self, port: u16) -> Option<&mut Connection> {
-        if self.owns_tcp_port(port) {
-            let c = self.get_mut_con(&port);
+    pub fn get_mut_by_sock(&mut self, sock: &(u32, u16)) -> Option<&mut Connection> {
+        if self.owns_ip(sock.0) && self.owns_tcp_port(sock.1) {
             // check if c is in use
-            if c.in_use() {
-                Some(c)
-            } else {
-                None
-            }
+            self.get_mut_con(sock).filter(|c| c.in_use())
         } else {
             None
         }
     }
 
-    //TODO allow for more precise time out conditions, currently whole TCP connections are timed out, also we should send a RST
-    pub fn release_timeouts(&mut self, now: &u64, wheel: &mut TimerWheel<u16>) {
-        loop {
-            match wheel.tick(now) {
-                (Some(mut drain), more) => {
-                    let mut port = drain.next();
-                    while port.is_some() {
-                        let p = port.unwrap();
-                        if p != 0 {
-                            self.timeout(p);
-                        }
-                        port = drain.next();
-                    }
-                    if !more {
-                        break;
-                    }
-                }
-                (None, more) => {
-                    if !more {
-                        break;
-                    }
+    //TODO allow for more precise time out conditions, currently whole TCP connections are timed out
+    /// releases timed out connections, the RSTs for the DUT are queued, see get_rst, with keepalive idle
+    /// connections are queued for a probe instead, see get_keepalive_connection,
+    /// returns the number of connections which are timed out, because the DUT did not answer the probes
+    pub fn release_timeouts(
+        &mut self,
+        now: &u64,
+        wheel: &mut TimerWheel<(u32, u16)>,
+        wheel_rto: &mut TimerWheel<(u32, u16)>,
+        keepalive: &Option<Keepalive>,
+    ) -> usize {
+        let mut probes = Vec::new();
+        let mut keepalive_timeouts = 0;
+        drain_wheel(wheel, now, |p| {
+            let keepalive_due = self
+                .get_mut_con(&p)
+                .map_or(false, |c| c.in_use() && c.keepalive_due(keepalive));
+            if keepalive_due {
+                probes.push(p);
+            } else if self.timeout(p, wheel_rto) == Some(TimeoutCause::Keepalive) {
+                keepalive_timeouts += 1;
+            }
+        });
+        if let Some(ref keepalive) = *keepalive {
+            for p in probes {
+                if let Some(c) = self.get_mut_con(&p) {
+                    c.wheel_slot_and_index = wheel.schedule(&keepalive.interval, p);
                 }
+                self.keepalive_ready.push_back(p);
             }
         }
+        keepalive_timeouts
     }
 
+    /// returns the timer which fired, None for the end of TIME_WAIT or a connection which is not in use
     #[inline]
-    fn timeout(&mut self, port: u16) {
+    fn timeout(&mut self, sock: (u32, u16), wheel_rto: &mut TimerWheel<(u32, u16)>) -> Option<TimeoutCause> {
         // the borrow checker makes things a little bit cumbersome:
         let mut in_use = false;
-        {
-            let c = self.get_mut_con(&port);
+        let mut time_wait = false;
+        let mut reset = None;
+        let mut cause = None;
+        if let Some(c) = self.get_mut_con(&sock) {
             if c.in_use() {
                 in_use = true;
-                c.set_release_cause(ReleaseCause::Timeout);
-                c.push_state(TcpState::Closed);
-                debug!("timing out port {} at {:?}", port, c.wheel_slot_and_index);
+                if c.in_time_wait() {
+                    time_wait = true;
+                } else {
+                    reset = c.reset();
+                    cause = Some(c.timeout_cause());
+                    c.set_timeout(cause.unwrap());
+                    c.push_state(TcpState::Closed);
+                }
+                c.stop_rto(wheel_rto, (0, 0));
+                debug!("timing out socket {:?} at {:?}", sock, c.wheel_slot_and_index);
                 // now we release the connection inline (cannot call self.release)
                 c.release();
             }
         }
         if in_use {
-            self.free_ports.push_back(port);
+            self.free(&sock);
+        }
+        if time_wait {
+            self.time_wait_ports -= 1;
         }
+        if let Some(reset) = reset {
+            self.rst_ready.push_back(reset);
+        }
+        cause
     }
 
     #[inline]
-    pub fn release(&mut self, port: u16, wheel: &mut TimerWheel<u16>) {
-        let c = &mut self.port2con[(port - self.tcp_port_base) as usize];
-        // only if it is in use, i.e. it has been not released already
-        if c.in_use() {
-            self.free_ports.push_back(port);
-            c.release();
-            //remove port from timer wheel by overwriting it
-            let old = wheel.replace(c.wheel_slot_and_index, 0);
-            assert_eq!(old.unwrap(), port);
-            // we keep unused connection in port2con table
+    pub fn release(
+        &mut self,
+        sock: (u32, u16),
+        wheel: &mut TimerWheel<(u32, u16)>,
+        wheel_rto: &mut TimerWheel<(u32, u16)>,
+    ) {
+        let mut in_use = false;
+        if let Some(c) = self.get_mut_con(&sock) {
+            // only if it is in use, i.e. it has been not released already
+            if c.in_use() {
+                in_use = true;
+                c.stop_rto(wheel_rto, (0, 0));
+                c.release();
+                //remove socket from timer wheel by overwriting it
+                let old = wheel.replace(c.wheel_slot_and_index, (0, 0));
+                assert_eq!(old.unwrap(), sock);
+            }
+        }
+        if in_use {
+            // we keep the unused connection for the next socket
+            self.free(&sock);
+        }
+    }
+
+    /// (re-)starts TIME_WAIT for an actively closed connection, the socket is released when the timer expires
+    pub fn time_wait(
+        &mut self,
+        sock: (u32, u16),
+        wheel: &mut TimerWheel<(u32, u16)>,
+        wheel_rto: &mut TimerWheel<(u32, u16)>,
+        duration: u64,
+    ) {
+        let mut time_wait_started = false;
+        if let Some(c) = self.get_mut_con(&sock) {
+            if c.in_use() {
+                if !c.time_wait {
+                    c.time_wait = true;
+                    c.stop_rto(wheel_rto, (0, 0));
+                    time_wait_started = true;
+                }
+                let old = wheel.replace(c.wheel_slot_and_index, (0, 0));
+                assert_eq!(old.unwrap(), sock);
+                c.wheel_slot_and_index = wheel.schedule(&duration, sock);
+            }
+        }
+        if time_wait_started {
+            self.time_wait_ports += 1;
+        }
+    }
+
+    /// queues connections with expired retransmission timer for retransmission,
+    /// connections which exceeded the maximum number of retransmissions are timed out,
+    /// returns the number of connections which are timed out
+    pub fn retransmission_timeouts(
+        &mut self,
+        now: &u64,
+        wheel_rto: &mut TimerWheel<(u32, u16)>,
+        wheel: &mut TimerWheel<(u32, u16)>,
+        rto_config: &RtoConfig,
+    ) -> usize {
+        let mut exhausted = Vec::new();
+        drain_wheel(wheel_rto, now, |p| {
+            if let Some(&slot) = self.sock2slot.get(&p) {
+                let c = &mut self.connections[slot];
+                if c.in_use() {
+                    c.rto_expired();
+                    if c.has_unacked() {
+                        if c.rto_backoffs() >= rto_config.max_retransmits {
+                            exhausted.push(p);
+                        } else {
+                            self.rtx_ready.push_back(p);
+                        }
+                    } else if c.persist_due() {
+                        self.persist_ready.push_back(p);
+                    }
+                }
+            }
+        });
+        let count = exhausted.len();
+        for p in exhausted {
+            let reset = {
+                let c = self.get_mut_con(&p).unwrap();
+                debug!("socket {:?}: giving up after {} retransmissions", p, c.rto_backoffs());
+                let reset = c.reset();
+                c.set_timeout(TimeoutCause::Retransmission);
+                c.push_state(TcpState::Closed);
+                reset
+            };
+            self.release(p, wheel, wheel_rto);
+            if let Some(reset) = reset {
+                self.rst_ready.push_back(reset);
+            }
+        }
+        count
+    }
+
+    /// queues a connection for a fast retransmission
+    #[inline]
+    pub fn set_rtx_connection(&mut self, sock: (u32, u16), ready_flag: &Arc<AtomicBool>) {
+        self.rtx_ready.push_back(sock);
+        if self.rtx_ready_connections() == 1 {
+            ready_flag.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// the next connection waiting for a retransmission
+    #[inline]
+    pub fn get_rtx_connection(&mut self) -> Option<&mut Connection> {
+        let (sock2slot, connections) = (&self.sock2slot, &self.connections);
+        // the connection may have been released or acknowledged in the meantime
+        let sock = pop_ready(&mut self.rtx_ready, |sock| {
+            sock2slot.get(sock).map_or(false, |&slot| {
+                let c = &connections[slot];
+                c.in_use() && c.has_unacked() && (!c.rto_running() || c.fast_rtx())
+            })
+        });
+        match sock {
+            Some(sock) => self.get_mut_con(&sock),
+            None => None,
+        }
+    }
+
+    #[inline]
+    pub fn rtx_ready_connections(&self) -> usize {
+        self.rtx_ready.len()
+    }
+
+    /// the next RST for a timed out connection
+    #[inline]
+    pub fn get_rst(&mut self) -> Option<Reset> {
+        self.rst_ready.pop_front()
+    }
+
+    #[inline]
+    pub fn rst_ready_connections(&self) -> usize {
+        self.rst_ready.len()
+    }
+
+    /// queues connections with an expired delayed ACK timer for a pure ACK, if their ACKs were not piggybacked
+    /// in the meantime
+    pub fn delayed_ack_timeouts(&mut self, now: &u64, wheel_ack: &mut TimerWheel<(u32, u16)>) {
+        drain_wheel(wheel_ack, now, |p| {
+            if let Some(&slot) = self.sock2slot.get(&p) {
+                let c = &mut self.connections[slot];
+                if c.in_use() {
+                    c.dack_expired();
+                    if c.acks_pending() {
+                        self.ack_ready.push_back(p);
+                    }
+                }
+            }
+        });
+    }
+
+    /// the next connection waiting for a delayed ACK
+    #[inline]
+    pub fn get_ack_connection(&mut self) -> Option<&mut Connection> {
+        let (sock2slot, connections) = (&self.sock2slot, &self.connections);
+        // the connection may have been released or the ACK piggybacked in the meantime
+        let sock = pop_ready(&mut self.ack_ready, |sock| {
+            sock2slot.get(sock).map_or(false, |&slot| {
+                let c = &connections[slot];
+                c.in_use() && c.acks_pending()
+            })
+        });
+        match sock {
+            Some(sock) => self.get_mut_con(&sock),
+            None => None,
+        }
+    }
+
+    #[inline]
+    pub fn ack_ready_connections(&self) -> usize {
+        self.ack_ready.len()
+    }
+
+    /// the next idle connection waiting for a keepalive probe
+    #[inline]
+    pub fn get_keepalive_connection(&mut self) -> Option<&mut Connection> {
+        let (sock2slot, connections) = (&self.sock2slot, &self.connections);
+        // the connection may have been released or answered by the DUT in the meantime
+        let sock = pop_ready(&mut self.keepalive_ready, |sock| {
+            sock2slot.get(sock).map_or(false, |&slot| {
+                let c = &connections[slot];
+                c.in_use() && c.keepalive_probes > 0
+            })
+        });
+        match sock {
+            Some(sock) => self.get_mut_con(&sock),
+            None => None,
+        }
+    }
+
+    #[inline]
+    pub fn keepalive_ready_connections(&self) -> usize {
+        self.keepalive_ready.len()
+    }
+
+    /// the next connection waiting for a window probe
+    #[inline]
+    pub fn get_persist_connection(&mut self) -> Option<&mut Connection> {
+        let (sock2slot, connections) = (&self.sock2slot, &self.connections);
+        // the connection may have been released or the DUT may have opened its window in the meantime
+        let sock = pop_ready(&mut self.persist_ready, |sock| {
+            sock2slot.get(sock).map_or(false, |&slot| {
+                let c = &connections[slot];
+                c.in_use() && c.persist_due()
+            })
+        });
+        match sock {
+            Some(sock) => self.get_mut_con(&sock),
+            None => None,
         }
     }
 
+    #[inline]
+    pub fn persist_ready_connections(&self) -> usize {
+        self.persist_ready.len()
+    }
+
     #[allow(dead_code)]
     pub fn dump_records(&mut self) {
         if self.c_record_store.is_some() {
@@ -516,19 +1756,33 @@ impl ConnectionManagerC {
             info!(
                 "{}: {:6} open connections",
                 self.pipeline_id,
-                self.port2con.iter().filter(|c| c.port() != 0).collect::<Vec<_>>().len()
+                self.sock2slot.len()
             );
         }
-        /*
-        self.port2con.iter().enumerate().for_each(|(i, c)| {
-            if c.port() != 0 {
-                // info!("{:6}: {}", i, c.con_rec())
-            }
-        });
-        */
     }
 
-    pub fn fetch_c_records(&mut self) -> Option<RecordStore<ConRecord>> {
+    /// moves the records out and replaces them with new stores, open connections are released before
+    pub fn fetch_records(&mut self) -> Option<EngineRecords> {
+        let con_records = self.fetch_c_records();
+        let ext_records = self.fetch_x_records();
+        con_records.map(|con_records| EngineRecords {
+            con_records,
+            ext_records: ext_records.unwrap_or_default(),
+        })
+    }
+
+    /// takes the extension records, to be called after fetch_c_records, which releases the open connections
+    fn fetch_x_records(&mut self) -> Option<ExtRecordStore> {
+        if self.x_record_store.is_some() {
+            let new_store = Rc::new(RefCell::new(ExtRecordStore::with_capacity(MAX_RECORDS)));
+            let store = mem::replace(self.x_record_store.as_mut().unwrap(), new_store);
+            Rc::try_unwrap(store).ok().map(|store| store.into_inner())
+        } else {
+            None
+        }
+    }
+
+    fn fetch_c_records(&mut self) -> Option<RecordStore<ConRecord>> {
         // we are "moving" the con_records out, and replace it with a new one
         if self.c_record_store.is_some() {
             let new_store = Rc::new(RefCell::new(RecordStore::with_capacity(MAX_RECORDS)));
@@ -536,7 +1790,7 @@ impl ConnectionManagerC {
             let strong_count = Rc::strong_count(&store);
             debug!("cm_s.fetch_c_records: strong_count= { }", strong_count);
             if strong_count > 1 {
-                for c in &mut self.port2con {
+                for c in &mut self.connections {
                     c.release();
                 }
             }
@@ -552,8 +1806,8 @@ impl ConnectionManagerC {
     }
 
     #[inline]
-    pub fn set_ready_connection(&mut self, port: u16, ready_flag: &Arc<AtomicBool>) {
-        self.ready.push_back(port);
+    pub fn set_ready_connection(&mut self, sock: (u32, u16), ready_flag: &Arc<AtomicBool>) {
+        self.ready.push_back(sock);
         // if this is the first ready connection, we restart the injector, avoid accessing Atomic unnecessarily
         if self.ready_connections() == 1 {
             ready_flag.store(true, Ordering::SeqCst);
@@ -567,38 +1821,39 @@ impl ConnectionManagerC {
 
     #[inline]
     pub fn get_ready_connection(&mut self) -> Option<&mut Connection> {
-        let mut port_result = None;
-        while port_result.is_none() {
-            match self.ready.pop_front() {
-                Some(port) => {
-                    let c = &self.port2con[(port - self.tcp_port_base) as usize];
-                    //trace!("found ready connection {}", if c.in_use() { c.port() } else { 0 });
-                    if c.in_use() && c.state() == TcpState::Established {
-                        port_result = Some(port)
-                    }
-                }
-                None => break, // ready queue is empty
-            };
-        }
+        let (sock2slot, connections) = (&self.sock2slot, &self.connections);
+        let sock = pop_ready(&mut self.ready, |sock| {
+            sock2slot.get(sock).map_or(false, |&slot| {
+                let c = &connections[slot];
+                //trace!("found ready connection {}", if c.in_use() { c.port() } else { 0 });
+                // in CLOSE-WAIT we may still send payload (half-close)
+                c.in_use() && (c.state() == TcpState::Established || c.state() == TcpState::CloseWait)
+            })
+        });
         // borrow checker forces us this two-step way, cannot mutably borrow connection directly
-        if let Some(port) = port_result {
-            Some(&mut self.port2con[(port - self.tcp_port_base) as usize])
-        } else {
-            None
+        match sock {
+            Some(sock) => self.get_mut_con(&sock),
+            None => None,
         }
     }
 }
 
-use netfcts::utils::Sock2Index as Sock2Index;
-use std::cmp;
-
 pub struct ConnectionManagerS {
     c_record_store: Option<Rc<RefCell<RecordStore<ConRecord>>>>,
+    x_record_store: Option<Rc<RefCell<ExtRecordStore>>>,
     sock2index: Sock2Index,
     //sock2index: HashMap<(u32,u16), u16>,
     //sock2index: BTreeMap<(u32,u16), u16>,
     connections: Vec<Connection>,
     free_slots: VecDeque<usize>,
+    /// sockets of connections with an expired retransmission timer
+    rtx_ready: VecDeque<(u32, u16)>,
+    /// timed out connections, which still must be reset
+    rst_ready: VecDeque<Reset>,
+    /// sockets of idle connections, which must send a keepalive probe
+    keepalive_ready: VecDeque<(u32, u16)>,
+    /// sockets of connections with reassembled payload, which is not yet echoed
+    delivery_ready: VecDeque<(u32, u16)>,
 }
 
 impl ConnectionManagerS {
@@ -608,13 +1863,23 @@ impl ConnectionManagerS {
         } else {
             None
         };
+        let x_store = if detailed_records {
+            Some(Rc::new(RefCell::new(ExtRecordStore::with_capacity(MAX_RECORDS))))
+        } else {
+            None
+        };
         ConnectionManagerS {
             c_record_store: store,
+            x_record_store: x_store,
             sock2index: Sock2Index::new(),
             //sock2index: HashMap::with_capacity(MAX_CONNECTIONS),
             //sock2index: BTreeMap::new(),
             connections: vec![Connection::new(); MAX_CONNECTIONS],
             free_slots: (1..MAX_CONNECTIONS).collect(), // we use index 0 to indicate unused slots
+            rtx_ready: VecDeque::with_capacity(MAX_CONNECTIONS),
+            rst_ready: VecDeque::with_capacity(MAX_CONNECTIONS),
+            keepalive_ready: VecDeque::with_capacity(MAX_CONNECTIONS),
+            delivery_ready: VecDeque::with_capacity(MAX_CONNECTIONS),
         }
     }
 
@@ -651,6 +1916,7 @@ impl ConnectionManagerS {
                     Some(*sock),
                     TcpRole::Server,
                     Rc::clone(&self.c_record_store.as_ref().unwrap()),
+                    Rc::clone(&self.x_record_store.as_ref().unwrap()),
                 );
             } else {
                 c.initialize(Some(*sock), TcpRole::Server)
@@ -662,7 +1928,12 @@ impl ConnectionManagerS {
     }
 
     #[inline]
-    pub fn release(&mut self, sock: &(u32, u16), wheel: &mut TimerWheel<(u32, u16)>) {
+    pub fn release(
+        &mut self,
+        sock: &(u32, u16),
+        wheel: &mut TimerWheel<(u32, u16)>,
+        wheel_rto: &mut TimerWheel<(u32, u16)>,
+    ) {
         let index = self.sock2index.remove(sock);
         if index.is_some() {
             let c = &mut self.connections[index.unwrap() as usize];
@@ -671,50 +1942,193 @@ impl ConnectionManagerS {
                 //remove port from timer wheel by overwriting it
                 let old = wheel.replace(c.wheel_slot_and_index, (0, 0));
                 assert_eq!(old.unwrap(), *sock);
+                c.stop_rto(wheel_rto, (0, 0));
             }
             c.release();
             // we keep unused connection in port2con table
         }
     }
 
-    //TODO allow for more precise time out conditions, currently whole TCP connections are timed out, also we should send a RST
-    pub fn release_timeouts(&mut self, now: &u64, wheel: &mut TimerWheel<(u32, u16)>) {
-        //trace!("cm server side: release_timeouts");
-        loop {
-            match wheel.tick(now) {
-                (Some(mut drain), more) => {
-                    let mut sock = drain.next();
-                    while sock.is_some() {
-                        let s = sock.unwrap();
-                        if s.1 != 0 {
-                            self.timeout(&s);
+    /// queues connections with expired retransmission timer for retransmission,
+    /// connections which exceeded the maximum number of retransmissions are timed out,
+    /// returns the number of connections which are timed out
+    pub fn retransmission_timeouts(
+        &mut self,
+        now: &u64,
+        wheel_rto: &mut TimerWheel<(u32, u16)>,
+        wheel: &mut TimerWheel<(u32, u16)>,
+        rto_config: &RtoConfig,
+    ) -> usize {
+        let mut exhausted = Vec::new();
+        drain_wheel(wheel_rto, now, |s| {
+            let index = self.sock2index.get(&s).map(|i| *i as usize);
+            if let Some(index) = index {
+                let c = &mut self.connections[index];
+                if c.in_use() {
+                    c.rto_expired();
+                    if c.has_unacked() {
+                        if c.rto_backoffs() >= rto_config.max_retransmits {
+                            exhausted.push(s);
+                        } else {
+                            self.rtx_ready.push_back(s);
                         }
-                        sock = drain.next();
-                    }
-                    if !more {
-                        break;
                     }
                 }
-                (None, more) => {
-                    if !more {
-                        break;
-                    }
+            }
+        });
+        let count = exhausted.len();
+        for s in exhausted {
+            let mut reset = None;
+            if let Some(c) = self.get_mut(&s) {
+                debug!("sock {:?}: giving up after {} retransmissions", s, c.rto_backoffs());
+                reset = c.reset();
+                c.set_timeout(TimeoutCause::Retransmission);
+                c.push_state(TcpState::Closed);
+            }
+            self.release(&s, wheel, wheel_rto);
+            if let Some(reset) = reset {
+                self.rst_ready.push_back(reset);
+            }
+        }
+        count
+    }
+
+    /// the next connection waiting for a retransmission
+    #[inline]
+    pub fn get_rtx_connection(&mut self) -> Option<&mut Connection> {
+        let (sock2index, connections) = (&self.sock2index, &self.connections);
+        // the connection may have been released or acknowledged in the meantime
+        let sock = pop_ready(&mut self.rtx_ready, |sock| {
+            sock2index.get(sock).map_or(false, |&index| {
+                let c = &connections[index as usize];
+                c.in_use() && c.has_unacked() && !c.rto_running()
+            })
+        });
+        match sock {
+            Some(sock) => self.get_mut(&sock),
+            None => None,
+        }
+    }
+
+    #[inline]
+    pub fn rtx_ready_connections(&self) -> usize {
+        self.rtx_ready.len()
+    }
+
+    /// the next RST for a timed out connection
+    #[inline]
+    pub fn get_rst(&mut self) -> Option<Reset> {
+        self.rst_ready.pop_front()
+    }
+
+    #[inline]
+    pub fn rst_ready_connections(&self) -> usize {
+        self.rst_ready.len()
+    }
+
+    /// the next idle connection waiting for a keepalive probe
+    #[inline]
+    pub fn get_keepalive_connection(&mut self) -> Option<&mut Connection> {
+        let (sock2index, connections) = (&self.sock2index, &self.connections);
+        // the connection may have been released or answered by the DUT in the meantime
+        let sock = pop_ready(&mut self.keepalive_ready, |sock| {
+            sock2index.get(sock).map_or(false, |&index| {
+                let c = &connections[index as usize];
+                c.in_use() && c.keepalive_probes > 0
+            })
+        });
+        match sock {
+            Some(sock) => self.get_mut(&sock),
+            None => None,
+        }
+    }
+
+    #[inline]
+    pub fn keepalive_ready_connections(&self) -> usize {
+        self.keepalive_ready.len()
+    }
+
+    /// queues a connection, which has reassembled payload to echo, see get_delivery_connection
+    #[inline]
+    pub fn set_delivery_ready(&mut self, sock: (u32, u16)) {
+        self.delivery_ready.push_back(sock);
+    }
+
+    /// the next connection, which echoes a segment of its reassembled payload
+    #[inline]
+    pub fn get_delivery_connection(&mut self) -> Option<&mut Connection> {
+        let (sock2index, connections) = (&self.sock2index, &self.connections);
+        // the connection may have been released or closed in the meantime
+        let sock = pop_ready(&mut self.delivery_ready, |sock| {
+            sock2index.get(sock).map_or(false, |&index| {
+                let c = &connections[index as usize];
+                c.in_use() && c.has_delivered() && c.state() == TcpState::Established
+            })
+        });
+        match sock {
+            Some(sock) => self.get_mut(&sock),
+            None => None,
+        }
+    }
+
+    #[inline]
+    pub fn delivery_ready_connections(&self) -> usize {
+        self.delivery_ready.len()
+    }
+
+    //TODO allow for more precise time out conditions, currently whole TCP connections are timed out
+    /// releases timed out connections, the RSTs for the DUT are queued, see get_rst, with keepalive idle
+    /// connections are queued for a probe instead, see get_keepalive_connection,
+    /// returns the number of connections which are timed out, because the DUT did not answer the probes
+    pub fn release_timeouts(
+        &mut self,
+        now: &u64,
+        wheel: &mut TimerWheel<(u32, u16)>,
+        wheel_rto: &mut TimerWheel<(u32, u16)>,
+        keepalive: &Option<Keepalive>,
+    ) -> usize {
+        //trace!("cm server side: release_timeouts");
+        let mut probes = Vec::new();
+        let mut keepalive_timeouts = 0;
+        drain_wheel(wheel, now, |s| {
+            let due = match self.get_mut(&s) {
+                Some(c) => c.in_use() && c.keepalive_due(keepalive),
+                None => false,
+            };
+            if due {
+                probes.push(s);
+            } else if self.timeout(&s, wheel_rto) == Some(TimeoutCause::Keepalive) {
+                keepalive_timeouts += 1;
+            }
+        });
+        if let Some(ref keepalive) = *keepalive {
+            for s in probes {
+                if let Some(c) = self.get_mut(&s) {
+                    c.wheel_slot_and_index = wheel.schedule(&keepalive.interval, s);
                 }
+                self.keepalive_ready.push_back(s);
             }
         }
+        keepalive_timeouts
     }
 
+    /// returns the timer which fired, None for a connection which is not in use
     #[inline]
-    fn timeout(&mut self, sock: &(u32, u16)) {
+    fn timeout(&mut self, sock: &(u32, u16), wheel_rto: &mut TimerWheel<(u32, u16)>) -> Option<TimeoutCause> {
         // the borrow checker makes things a little bit cumbersome:
         let mut in_use = false;
+        let mut reset = None;
+        let mut cause = None;
         {
             let opt_c = self.get_mut(sock);
             if let Some(c) = opt_c {
                 in_use = c.in_use();
                 if in_use {
-                    c.set_release_cause(ReleaseCause::Timeout);
+                    reset = c.reset();
+                    cause = Some(c.timeout_cause());
+                    c.set_timeout(cause.unwrap());
                     c.push_state(TcpState::Closed);
+                    c.stop_rto(wheel_rto, (0, 0));
                     c.release();
                 }
             }
@@ -723,9 +2137,34 @@ impl ConnectionManagerS {
             let index = self.sock2index.remove(sock);
             self.free_slots.push_back(index.unwrap() as usize);
         }
+        if let Some(reset) = reset {
+            self.rst_ready.push_back(reset);
+        }
+        cause
+    }
+
+    /// moves the records out and replaces them with new stores, open connections are released before
+    pub fn fetch_records(&mut self) -> Option<EngineRecords> {
+        let con_records = self.fetch_c_records();
+        let ext_records = self.fetch_x_records();
+        con_records.map(|con_records| EngineRecords {
+            con_records,
+            ext_records: ext_records.unwrap_or_default(),
+        })
+    }
+
+    /// takes the extension records, to be called after fetch_c_records, which releases the open connections
+    fn fetch_x_records(&mut self) -> Option<ExtRecordStore> {
+        if self.x_record_store.is_some() {
+            let new_store = Rc::new(RefCell::new(ExtRecordStore::with_capacity(MAX_RECORDS)));
+            let store = mem::replace(self.x_record_store.as_mut().unwrap(), new_store);
+            Rc::try_unwrap(store).ok().map(|store| store.into_inner())
+        } else {
+            None
+        }
     }
 
-    pub fn fetch_c_records(&mut self) -> Option<RecordStore<ConRecord>> {
+    fn fetch_c_records(&mut self) -> Option<RecordStore<ConRecord>> {
         if self.c_record_store.is_some() {
             // we are "moving" the con_records out, and replace it with a new one
             let new_store = Rc::new(RefCell::new(RecordStore::with_capacity(MAX_RECORDS)));
@@ -748,3 +2187,29 @@ impl ConnectionManagerS {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reassemble_stops_in_front_of_missing_payload() {
+        let mut c = Connection::new();
+        c.ackn_nxt = 1000;
+        c.rcv_wnd = 5840;
+        assert!(c.queue_out_of_order(1100, &[1u8; 100]));
+        c.sack_blocks.insert(1100, 1200);
+        assert!(c.queue_out_of_order(1300, &[3u8; 100]));
+        c.sack_blocks.insert(1300, 1400);
+        // in-order payload up to 1100 fills the first gap only
+        c.ackn_nxt = 1100;
+        assert_eq!(c.reassemble(), 1);
+        assert_eq!(c.ackn_nxt, 1200);
+        assert_eq!(c.sack_blocks.as_slice(), &[(1300, 1400)]);
+        assert_eq!(c.pop_delivered(), Some(vec![1u8; 100]));
+        assert!(!c.has_delivered());
+        // nothing is delivered, while [1200, 1300) is missing
+        assert_eq!(c.reassemble(), 0);
+        assert_eq!(c.ackn_nxt, 1200);
+    }
+}
@@ -0,0 +1,340 @@
+use std::cmp;
+use std::fmt;
+use std::u32;
+
+/// sender maximum segment size, as long as we do not negotiate the MSS option
+pub const DEFAULT_MSS: u32 = 1460;
+
+/// number of duplicate ACKs which trigger a fast retransmission (RFC 5681)
+pub const DUP_ACK_THRESHOLD: u8 = 3;
+
+/// constants of RFC 8312
+const CUBIC_C: f64 = 0.4;
+const CUBIC_BETA: f64 = 0.7;
+
+/// the congestion control algorithm, selected per target
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CongestionControlType {
+    NewReno,
+    Cubic,
+}
+
+impl Default for CongestionControlType {
+    fn default() -> CongestionControlType {
+        CongestionControlType::NewReno
+    }
+}
+
+/// interface of the congestion control algorithms, windows are counted in bytes and times in cpu cycles
+pub trait CongestionControl: fmt::Debug {
+    fn algorithm(&self) -> CongestionControlType;
+
+    /// resets the state for a new connection
+    fn init(&mut self, mss: u32);
+
+    fn cwnd(&self) -> u32;
+
+    fn ssthresh(&self) -> u32;
+
+    /// acked bytes of new data were acknowledged at time now, not called during fast recovery
+    fn on_ack(&mut self, acked: u32, now: u64);
+
+    /// loss detected by duplicate ACKs, we enter fast recovery
+    fn on_congestion(&mut self, in_flight: u32, now: u64);
+
+    /// the retransmission timer expired
+    fn on_timeout(&mut self, in_flight: u32, now: u64);
+}
+
+pub fn new_congestion_control(algorithm: CongestionControlType, mss: u32, cpu_clock: u64) -> Box<dyn CongestionControl> {
+    let mut cc: Box<dyn CongestionControl> = match algorithm {
+        CongestionControlType::NewReno => Box::new(NewReno::new()),
+        CongestionControlType::Cubic => Box::new(Cubic::new(cpu_clock)),
+    };
+    cc.init(mss);
+    cc
+}
+
+/// initial window according to RFC 5681, 3.1
+#[inline]
+fn initial_window(mss: u32) -> u32 {
+    cmp::min(4 * mss, cmp::max(2 * mss, 4380))
+}
+
+/// slow start and congestion avoidance of RFC 5681 with appropriate byte counting (RFC 3465),
+/// the fast recovery of RFC 6582 is done by the connection
+#[derive(Debug, Clone)]
+pub struct NewReno {
+    mss: u32,
+    cwnd: u32,
+    ssthresh: u32,
+    /// bytes acknowledged in congestion avoidance since the last increase of cwnd
+    bytes_acked: u32,
+}
+
+impl NewReno {
+    pub fn new() -> NewReno {
+        NewReno {
+            mss: DEFAULT_MSS,
+            cwnd: initial_window(DEFAULT_MSS),
+            ssthresh: u32::MAX,
+            bytes_acked: 0,
+        }
+    }
+}
+
+impl CongestionControl for NewReno {
+    fn algorithm(&self) -> CongestionControlType {
+        CongestionControlType::NewReno
+    }
+
+    fn init(&mut self, mss: u32) {
+        self.mss = mss;
+        self.cwnd = initial_window(mss);
+        self.ssthresh = u32::MAX;
+        self.bytes_acked = 0;
+    }
+
+    #[inline]
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    #[inline]
+    fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    fn on_ack(&mut self, acked: u32, _now: u64) {
+        if self.cwnd < self.ssthresh {
+            self.cwnd = self.cwnd.saturating_add(cmp::min(acked, self.mss));
+        } else {
+            self.bytes_acked += acked;
+            if self.bytes_acked >= self.cwnd {
+                self.bytes_acked -= self.cwnd;
+                self.cwnd = self.cwnd.saturating_add(self.mss);
+            }
+        }
+    }
+
+    fn on_congestion(&mut self, in_flight: u32, _now: u64) {
+        self.ssthresh = cmp::max(in_flight / 2, 2 * self.mss);
+        self.cwnd = self.ssthresh;
+        self.bytes_acked = 0;
+    }
+
+    fn on_timeout(&mut self, in_flight: u32, _now: u64) {
+        self.ssthresh = cmp::max(in_flight / 2, 2 * self.mss);
+        self.cwnd = self.mss;
+        self.bytes_acked = 0;
+    }
+}
+
+/// CUBIC according to RFC 8312, including fast convergence and the TCP-friendly region
+#[derive(Debug, Clone)]
+pub struct Cubic {
+    mss: u32,
+    cwnd: u32,
+    ssthresh: u32,
+    /// window before the last reduction, in bytes
+    w_max: f64,
+    /// window which standard TCP would reach, in bytes
+    w_est: f64,
+    /// time to reach w_max in seconds
+    k: f64,
+    /// start of the current congestion avoidance epoch in cycles, 0 if no epoch is running
+    epoch_start: u64,
+    /// fraction of a byte, which is not yet added to cwnd
+    pending: f64,
+    cpu_clock: u64,
+}
+
+impl Cubic {
+    pub fn new(cpu_clock: u64) -> Cubic {
+        Cubic {
+            mss: DEFAULT_MSS,
+            cwnd: initial_window(DEFAULT_MSS),
+            ssthresh: u32::MAX,
+            w_max: 0.0,
+            w_est: 0.0,
+            k: 0.0,
+            epoch_start: 0,
+            pending: 0.0,
+            cpu_clock,
+        }
+    }
+
+    /// reduces the window and remembers w_max, returns the new ssthresh
+    fn reduce(&mut self) -> u32 {
+        let cwnd = self.cwnd as f64;
+        self.w_max = if cwnd < self.w_max {
+            // fast convergence
+            cwnd * (1.0 + CUBIC_BETA) / 2.0
+        } else {
+            cwnd
+        };
+        self.epoch_start = 0;
+        self.pending = 0.0;
+        cmp::max((cwnd * CUBIC_BETA) as u32, 2 * self.mss)
+    }
+}
+
+impl CongestionControl for Cubic {
+    fn algorithm(&self) -> CongestionControlType {
+        CongestionControlType::Cubic
+    }
+
+    fn init(&mut self, mss: u32) {
+        self.mss = mss;
+        self.cwnd = initial_window(mss);
+        self.ssthresh = u32::MAX;
+        self.w_max = 0.0;
+        self.w_est = 0.0;
+        self.k = 0.0;
+        self.epoch_start = 0;
+        self.pending = 0.0;
+    }
+
+    #[inline]
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    #[inline]
+    fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    fn on_ack(&mut self, acked: u32, now: u64) {
+        if self.cwnd < self.ssthresh {
+            self.cwnd = self.cwnd.saturating_add(cmp::min(acked, self.mss));
+            return;
+        }
+        let mss = self.mss as f64;
+        let cwnd = self.cwnd as f64;
+        if self.epoch_start == 0 {
+            self.epoch_start = cmp::max(now, 1);
+            if cwnd < self.w_max {
+                self.k = ((self.w_max - cwnd) / mss / CUBIC_C).cbrt();
+            } else {
+                self.k = 0.0;
+                self.w_max = cwnd;
+            }
+            self.w_est = cwnd;
+        }
+        let t = now.saturating_sub(self.epoch_start) as f64 / self.cpu_clock as f64;
+        let w_cubic = (CUBIC_C * (t - self.k).powi(3)) * mss + self.w_max;
+        // growth is limited to 1.5 * cwnd per RTT
+        let target = w_cubic.max(cwnd).min(1.5 * cwnd);
+        self.w_est += 3.0 * (1.0 - CUBIC_BETA) / (1.0 + CUBIC_BETA) * mss * acked as f64 / cwnd;
+        let increment = if self.w_est > target {
+            // TCP-friendly region
+            (self.w_est - cwnd) * acked as f64 / cwnd
+        } else {
+            (target - cwnd) * acked as f64 / cwnd
+        };
+        self.pending += increment.max(0.0);
+        let whole = self.pending.floor();
+        self.pending -= whole;
+        self.cwnd = self.cwnd.saturating_add(whole as u32);
+    }
+
+    fn on_congestion(&mut self, _in_flight: u32, _now: u64) {
+        self.ssthresh = self.reduce();
+        self.cwnd = self.ssthresh;
+    }
+
+    fn on_timeout(&mut self, _in_flight: u32, _now: u64) {
+        self.ssthresh = self.reduce();
+        self.cwnd = self.mss;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MSS: u32 = 1000;
+    const CPU_CLOCK: u64 = 1_000_000;
+
+    #[test]
+    fn initial_window_depends_on_mss() {
+        assert_eq!(initial_window(536), 2144);
+        assert_eq!(initial_window(1000), 4000);
+        assert_eq!(initial_window(1460), 4380);
+        assert_eq!(initial_window(9000), 18000);
+    }
+
+    #[test]
+    fn new_reno_follows_rfc_5681() {
+        let mut cc = new_congestion_control(CongestionControlType::NewReno, MSS, CPU_CLOCK);
+        assert_eq!(cc.algorithm(), CongestionControlType::NewReno);
+        assert_eq!(cc.cwnd(), 4000);
+        assert_eq!(cc.ssthresh(), u32::MAX);
+        // slow start grows by at most one MSS per ACK
+        cc.on_ack(1500, 0);
+        assert_eq!(cc.cwnd(), 5000);
+        cc.on_ack(500, 0);
+        assert_eq!(cc.cwnd(), 5500);
+        cc.on_congestion(10000, 0);
+        assert_eq!(cc.ssthresh(), 5000);
+        assert_eq!(cc.cwnd(), 5000);
+        // congestion avoidance grows by one MSS per window of acknowledged bytes
+        cc.on_ack(2500, 0);
+        assert_eq!(cc.cwnd(), 5000);
+        cc.on_ack(2500, 0);
+        assert_eq!(cc.cwnd(), 6000);
+        cc.on_timeout(2000, 0);
+        assert_eq!(cc.ssthresh(), 2000);
+        assert_eq!(cc.cwnd(), 1000);
+        // back in slow start
+        cc.on_ack(1000, 0);
+        assert_eq!(cc.cwnd(), 2000);
+    }
+
+    #[test]
+    fn cubic_reduces_by_beta() {
+        let mut cc = Cubic::new(CPU_CLOCK);
+        cc.init(MSS);
+        assert_eq!(cc.cwnd(), 4000);
+        for _ in 0..6 {
+            cc.on_ack(1000, 0);
+        }
+        assert_eq!(cc.cwnd(), 10000);
+        cc.on_congestion(10000, 0);
+        assert_eq!(cc.ssthresh(), 7000);
+        assert_eq!(cc.cwnd(), 7000);
+        assert_eq!(cc.w_max, 10000.0);
+        // the first ACK in congestion avoidance starts the epoch, K is the time to reach w_max again
+        cc.on_ack(1000, CPU_CLOCK);
+        assert!((cc.k - 7.5f64.cbrt()).abs() < 1e-9);
+        let cwnd = cc.cwnd();
+        for i in 1..10 {
+            cc.on_ack(1000, CPU_CLOCK + i * CPU_CLOCK / 100);
+        }
+        assert!(cc.cwnd() > cwnd);
+        // fast convergence, the window did not reach w_max
+        let cwnd = cc.cwnd();
+        assert!(cwnd < 10000);
+        cc.on_congestion(cwnd, 2 * CPU_CLOCK);
+        assert_eq!(cc.w_max, cwnd as f64 * 0.85);
+        assert_eq!(cc.ssthresh(), (cwnd as f64 * CUBIC_BETA) as u32);
+        assert_eq!(cc.epoch_start, 0);
+    }
+
+    #[test]
+    fn cubic_timeout_restarts_slow_start() {
+        let mut cc = new_congestion_control(CongestionControlType::Cubic, MSS, CPU_CLOCK);
+        assert_eq!(cc.algorithm(), CongestionControlType::Cubic);
+        cc.on_timeout(4000, 0);
+        assert_eq!(cc.ssthresh(), 2800);
+        assert_eq!(cc.cwnd(), 1000);
+        cc.on_ack(1000, 0);
+        assert_eq!(cc.cwnd(), 2000);
+        // ssthresh is at least two segments
+        let mut cc = new_congestion_control(CongestionControlType::Cubic, MSS, CPU_CLOCK);
+        cc.on_timeout(1000, 0);
+        cc.on_timeout(1000, 0);
+        assert_eq!(cc.ssthresh(), 2000);
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use tcp_options::FastOpenCookie;
+
+/// length of the cookies, which we issue as server
+const COOKIE_LEN: usize = 8;
+
+/// issues and validates the cookies of the server, a cookie is a MAC of the client IP address (RFC 7413, 4.1.2),
+/// here SipHash keyed with the secret
+pub struct CookieGenerator {
+    secret: RandomState,
+}
+
+impl CookieGenerator {
+    pub fn new(secret: RandomState) -> CookieGenerator {
+        CookieGenerator { secret }
+    }
+
+    pub fn cookie(&self, client_ip: u32) -> FastOpenCookie {
+        let mut hasher = self.secret.build_hasher();
+        client_ip.hash(&mut hasher);
+        FastOpenCookie::new(&hasher.finish().to_be_bytes()[..COOKIE_LEN])
+    }
+
+    #[inline]
+    pub fn is_valid(&self, client_ip: u32, cookie: &FastOpenCookie) -> bool {
+        *cookie == self.cookie(client_ip)
+    }
+}
+
+/// the cookies which the client received from its targets, indexed like the targets
+pub struct CookieCache {
+    cookies: Vec<Option<FastOpenCookie>>,
+}
+
+impl CookieCache {
+    pub fn new(no_targets: usize) -> CookieCache {
+        CookieCache {
+            cookies: vec![None; no_targets],
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, target: usize) -> Option<&FastOpenCookie> {
+        self.cookies[target].as_ref()
+    }
+
+    #[inline]
+    pub fn insert(&mut self, target: usize, cookie: FastOpenCookie) {
+        self.cookies[target] = Some(cookie);
+    }
+
+    #[inline]
+    pub fn remove(&mut self, target: usize) {
+        self.cookies[target] = None;
+    }
+}
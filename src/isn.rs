@@ -0,0 +1,59 @@
+use std::cmp;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+/// generates initial sequence numbers according to RFC 6528:
+/// ISN = M + F(localip, localport, remoteip, remoteport, secretkey),
+/// M is a timer which ticks every 4 microseconds, F is SipHash keyed with a random secret of the generator
+pub struct IsnGenerator {
+    secret: RandomState,
+    /// cycles per tick of the timer M
+    cycles_per_tick: u64,
+}
+
+impl IsnGenerator {
+    pub fn new(cpu_clock: u64) -> IsnGenerator {
+        IsnGenerator {
+            secret: RandomState::new(),
+            cycles_per_tick: cmp::max(cpu_clock / 250_000, 1),
+        }
+    }
+
+    /// the ISN for a connection between the local and the remote socket, now is a time stamp in cycles
+    pub fn isn(&self, local: (u32, u16), remote: (u32, u16), now: u64) -> u32 {
+        let mut hasher = self.secret.build_hasher();
+        local.hash(&mut hasher);
+        remote.hash(&mut hasher);
+        let m = (now / self.cycles_per_tick) as u32;
+        m.wrapping_add(hasher.finish() as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CPU_CLOCK: u64 = 1_000_000_000;
+
+    #[test]
+    fn isn_depends_on_sockets_and_time() {
+        let generator = IsnGenerator::new(CPU_CLOCK);
+        let local = (0xc0a8_de02, 50000);
+        let remote = (0xc0a8_de20, 80);
+        let isn = generator.isn(local, remote, 0);
+        assert_eq!(generator.isn(local, remote, 0), isn);
+        // M ticks every 4 microseconds, i.e. every 4000 cycles at 1 GHz
+        assert_eq!(generator.isn(local, remote, 3_999), isn);
+        assert_eq!(generator.isn(local, remote, 4_000), isn.wrapping_add(1));
+        assert_eq!(generator.isn(local, remote, 4_000_000), isn.wrapping_add(1000));
+        assert_ne!(generator.isn(local, (0xc0a8_de20, 81), 0), isn);
+    }
+
+    #[test]
+    fn generators_have_distinct_secrets() {
+        let local = (0xc0a8_de02, 50000);
+        let remote = (0xc0a8_de20, 80);
+        let isns: Vec<u32> = (0..4).map(|_| IsnGenerator::new(CPU_CLOCK).isn(local, remote, 0)).collect();
+        assert!(isns.iter().any(|&isn| isn != isns[0]));
+    }
+}
@@ -24,29 +24,43 @@ extern crate core;
 pub mod nftraffic;
 pub mod run_test;
 mod cmanager;
+mod retransmission;
+mod statistics;
+mod congestion;
+mod tcp_options;
+mod reassembly;
+mod isn;
+mod fastopen;
+mod arp;
 
 pub use netfcts::tcp_common::{CData, L234Data, ReleaseCause, UserData, TcpRole, TcpState, TcpCounter, TcpStatistics};
 pub use netfcts::conrecord::ConRecord;
 pub use netfcts::recstore::TEngineStore;
 
-pub use cmanager::{ Connection};
+pub use cmanager::{ Connection, TimeoutCause, EngineRecords, ExtRecord};
+pub use statistics::{TcpExtStatistics, TcpExtCounter};
+pub use congestion::{CongestionControl, CongestionControlType};
 
 use eui48::MacAddress;
 use uuid::Uuid;
+use ipnet::Ipv4Net;
 
 use e2d2::scheduler::*;
-use e2d2::interface::{ PmdPort, Pdu, };
+use e2d2::interface::{ PmdPort, Pdu, FlowSteeringMode};
 
 use nftraffic::setup_generator;
+use arp::NeighbourCache;
 use netfcts::tasks::*;
 use netfcts::comm::{MessageFrom, MessageTo, PipelineId};
 use netfcts::{new_port_queues_for_core, physical_ports_for_core, RunConfiguration};
-use netfcts::utils::Timeouts;
 
 use std::net::Ipv4Addr;
 use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
 use std::sync::Arc;
 
+/// sets the payload of the client, either directly in the Pdu, or for messages which do not fit into a single
+/// segment with Connection::send_message, sets the bool to close the connection with a FIN
 pub trait FnPayload =
     Fn(&mut Pdu, &mut Connection, Option<CData>, &mut bool) -> usize + Sized + Send + Sync + Clone + 'static;
 
@@ -56,40 +70,306 @@ pub struct Configuration {
     pub targets: Vec<TargetConfig>,
     pub engine: EngineConfig,
     pub test_size: Option<usize>,
+    /// static routes for targets outside the local subnet
+    pub routes: Option<Vec<RouteConfig>>,
+    /// next hop for targets, which match no route
+    pub default_gateway: Option<Ipv4Addr>,
+}
+
+impl Configuration {
+    /// the next hop for target, i.e. the gateway of the longest matching prefix, or the default gateway,
+    /// or the target itself, if it is directly connected
+    pub fn next_hop(&self, target: Ipv4Addr) -> Ipv4Addr {
+        let route = self.routes.as_ref().and_then(|routes| {
+            routes
+                .iter()
+                .filter(|route| route.prefix.contains(&target))
+                .max_by_key(|route| route.prefix.prefix_len())
+        });
+        match route {
+            Some(route) => route.gateway.unwrap_or(target),
+            None => self.default_gateway.unwrap_or(target),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct RouteConfig {
+    pub prefix: Ipv4Net,
+    /// a prefix without gateway is directly connected, e.g. the local subnet, if there is a default gateway
+    pub gateway: Option<Ipv4Addr>,
 }
 
 
 #[derive(Deserialize, Clone)]
 pub struct EngineConfig {
-    pub timeouts: Option<Timeouts>,
+    pub timeouts: Option<TcpTimeouts>,
     pub port: u16,
     pub cps_limit: Option<u64>,
     pub max_open: Option<usize>,
     pub detailed_records: Option<bool>,
     pub fin_by_client: Option<usize>,
     pub fin_by_server: Option<usize>,
+    /// initial retransmission timeout in millis
+    pub rto_initial: Option<u64>,
+    /// lower bound of the retransmission timeout in millis
+    pub rto_min: Option<u64>,
+    /// upper bound of the retransmission timeout in millis
+    pub rto_max: Option<u64>,
+    /// number of retransmissions of a segment, before the connection is timed out
+    pub max_retransmits: Option<u8>,
+    /// receive window in bytes, which we advertise to the DUT, values above 65535 require window scaling
+    pub rcv_wnd: Option<u32>,
+    /// the MSS which we announce
+    pub mss: Option<u16>,
+    /// offer the window scale option (RFC 7323)
+    pub window_scaling: Option<bool>,
+    /// offer the SACK-permitted option (RFC 2018)
+    pub sack: Option<bool>,
+    /// offer the timestamps option (RFC 7323)
+    pub timestamps: Option<bool>,
+    /// duration of TIME_WAIT (2 MSL) in millis, during which the port of an actively closed client connection
+    /// is not reused, default is 0, which releases the port immediately
+    pub time_wait: Option<u64>,
+    /// forward segments, which do not belong to a connection, to the KNI i/f instead of answering them with a RST
+    pub rst_via_kni: Option<bool>,
+    /// Nagle's algorithm (RFC 896) for the payload of the client
+    pub nagle: Option<bool>,
+    /// TCP Fast Open (RFC 7413): the client requests cookies and sends its first payload in the SYN,
+    /// the server issues cookies and accepts data in SYNs with a valid cookie
+    pub fast_open: Option<bool>,
+    /// ECN (RFC 3168): the client requests ECN in its SYN, the server agrees if the DUT requests it,
+    /// new data is sent ECT(0), CE marks are echoed with ECE and ECE reduces the congestion window
+    pub ecn: Option<bool>,
+    /// keepalive probes (RFC 1122, 4.2.3.6) for idle connections in ESTABLISHED, without this idle connections
+    /// time out with the timeout of ESTABLISHED
+    pub keepalive: Option<KeepaliveConfig>,
+    /// lifetime in millis of the MAC addresses of targets, which are resolved with ARP
+    pub arp_timeout: Option<u64>,
+    /// source addresses of the client connections, without a pool the client uses the address of the pipeline,
+    /// the pool is shared by the pipelines, which use distinct port ranges, i.e. flow steering must be by port,
+    /// see check_client_pool
+    pub client_pool: Option<Ipv4Net>,
+    /// key of the cookies, which the server issues, the same for all pipelines of a run
+    #[serde(skip)]
+    pub fast_open_secret: RandomState,
+    /// MAC addresses resolved with ARP, shared by all pipelines of a run
+    #[serde(skip)]
+    pub neighbours: NeighbourCache,
 }
 
 impl EngineConfig {
+    pub fn timeouts(&self) -> TcpTimeouts {
+        self.timeouts.unwrap_or_default()
+    }
+
     pub fn cps_limit(&self) -> u64 {
         self.cps_limit.unwrap_or(10000000)
     }
+
+    pub fn rto_initial(&self) -> u64 {
+        self.rto_initial.unwrap_or(1000)
+    }
+
+    pub fn rto_min(&self) -> u64 {
+        self.rto_min.unwrap_or(200)
+    }
+
+    pub fn rto_max(&self) -> u64 {
+        self.rto_max.unwrap_or(8000)
+    }
+
+    pub fn max_retransmits(&self) -> u8 {
+        self.max_retransmits.unwrap_or(5)
+    }
+
+    pub fn rcv_wnd(&self) -> u32 {
+        self.rcv_wnd.unwrap_or(5840) // 4* MSS(1460)
+    }
+
+    pub fn mss(&self) -> u16 {
+        self.mss.unwrap_or(1460)
+    }
+
+    pub fn window_scaling(&self) -> bool {
+        self.window_scaling.unwrap_or(true)
+    }
+
+    pub fn sack(&self) -> bool {
+        self.sack.unwrap_or(true)
+    }
+
+    pub fn timestamps(&self) -> bool {
+        self.timestamps.unwrap_or(true)
+    }
+
+    pub fn time_wait(&self) -> u64 {
+        self.time_wait.unwrap_or(0)
+    }
+
+    pub fn rst_via_kni(&self) -> bool {
+        self.rst_via_kni.unwrap_or(false)
+    }
+
+    pub fn nagle(&self) -> bool {
+        self.nagle.unwrap_or(false)
+    }
+
+    pub fn fast_open(&self) -> bool {
+        self.fast_open.unwrap_or(false)
+    }
+
+    pub fn ecn(&self) -> bool {
+        self.ecn.unwrap_or(false)
+    }
+
+    pub fn arp_timeout(&self) -> u64 {
+        self.arp_timeout.unwrap_or(60000)
+    }
+
+    /// the pipelines of a port share the client pool and are told apart by their port range, with flow steering
+    /// by IP address the replies of the DUT would not reach the pipeline which owns the connection
+    pub fn check_client_pool(&self, flow_steering: FlowSteeringMode) -> Result<(), String> {
+        match (self.client_pool, flow_steering) {
+            (Some(pool), FlowSteeringMode::Ip) => Err(format!("client_pool {} requires flow steering by port", pool)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// per-state timeouts of a connection in millis, the closing states default to the idle timeout of ESTABLISHED,
+/// the handshake states as well
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+pub struct TcpTimeouts {
+    /// idle time in ESTABLISHED
+    pub established: Option<u64>,
+    pub syn_sent: Option<u64>,
+    pub syn_received: Option<u64>,
+    /// FIN-WAIT-1, FIN-WAIT-2, CLOSING, CLOSE-WAIT and LAST-ACK
+    pub fin_wait: Option<u64>,
+}
+
+impl TcpTimeouts {
+    pub fn established(&self) -> u64 {
+        self.established.unwrap_or(200)
+    }
+
+    pub fn syn_sent(&self) -> u64 {
+        self.syn_sent.unwrap_or(self.established())
+    }
+
+    pub fn syn_received(&self) -> u64 {
+        self.syn_received.unwrap_or(self.established())
+    }
+
+    pub fn fin_wait(&self) -> u64 {
+        self.fin_wait.unwrap_or(self.established())
+    }
+
+    /// replaces the timeout of ESTABLISHED, the timeouts of the other states are kept, even if they default to it
+    pub fn with_established(&self, established: u64) -> TcpTimeouts {
+        TcpTimeouts {
+            established: Some(established),
+            syn_sent: Some(self.syn_sent()),
+            syn_received: Some(self.syn_received()),
+            fin_wait: Some(self.fin_wait()),
+        }
+    }
+
+    /// the timeout for a connection in state
+    pub fn for_state(&self, state: TcpState) -> u64 {
+        match state {
+            TcpState::SynSent => self.syn_sent(),
+            TcpState::SynReceived | TcpState::Listen => self.syn_received(),
+            TcpState::Established => self.established(),
+            _ => self.fin_wait(),
+        }
+    }
+
+    /// limits all timeouts to max, returns true if a timeout was reduced
+    pub fn limit(&mut self, max: u64) -> bool {
+        let mut reduced = false;
+        for timeout in [
+            &mut self.established,
+            &mut self.syn_sent,
+            &mut self.syn_received,
+            &mut self.fin_wait,
+        ]
+        .iter_mut()
+        {
+            if timeout.map_or(false, |t| t > max) {
+                **timeout = Some(max);
+                reduced = true;
+            }
+        }
+        reduced
+    }
+}
+
+/// delayed ACKs of the client (RFC 1122, 4.2.3.2), only pure ACKs are delayed, ACKs are still piggybacked on our data
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+pub struct DelayedAckConfig {
+    /// number of payload segments of the DUT, which are acknowledged by one ACK
+    pub frequency: Option<u16>,
+    /// maximum delay of an ACK in millis
+    pub timeout: Option<u64>,
+}
+
+impl DelayedAckConfig {
+    pub fn frequency(&self) -> u16 {
+        self.frequency.unwrap_or(2)
+    }
+
+    pub fn timeout(&self) -> u64 {
+        self.timeout.unwrap_or(40)
+    }
+}
+
+/// keepalive probes of client and server, the idle time replaces the timeout of ESTABLISHED, a connection which
+/// gets no reply to its probes is timed out
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+pub struct KeepaliveConfig {
+    /// idle time in millis before the first probe
+    pub idle: Option<u64>,
+    /// time between probes in millis
+    pub interval: Option<u64>,
+    /// number of unanswered probes, before the connection is timed out
+    pub probes: Option<u8>,
+}
+
+impl KeepaliveConfig {
+    pub fn idle(&self) -> u64 {
+        self.idle.unwrap_or(5000)
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval.unwrap_or(1000)
+    }
+
+    pub fn probes(&self) -> u8 {
+        self.probes.unwrap_or(3)
+    }
 }
 
 #[derive(Deserialize, Clone)]
 pub struct TargetConfig {
     pub id: String,
     pub ip: Ipv4Addr,
+    /// without mac and linux_if the MAC of the target, or of its gateway, is resolved with ARP
     pub mac: Option<MacAddress>,
     pub linux_if: Option<String>,
     pub port: u16,
+    /// congestion control of the client for connections to this target, default is NewReno
+    pub congestion_control: Option<CongestionControlType>,
+    /// delayed ACKs of the client for connections to this target, without this payload is acknowledged immediately
+    pub delayed_ack: Option<DelayedAckConfig>,
 }
 
 pub fn setup_pipelines<FPL>(
     core: i32,
     pmd_ports: HashMap<String, Arc<PmdPort>>,
     sched: &mut StandaloneScheduler,
-    run_configuration: RunConfiguration<Configuration, TEngineStore>,
+    run_configuration: RunConfiguration<Configuration, EngineRecords>,
     servers: Vec<L234Data>,
     f_set_payload: Box<FPL>,
 ) where
@@ -157,3 +437,94 @@ pub fn setup_pipelines<FPL>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engine_options_parse() {
+        let value: toml::Value = toml::from_str(include_str!("../tests/test_gen_options.toml")).unwrap();
+        let config: Configuration = value["engine"].clone().try_into().unwrap();
+        assert_eq!(config.targets.len(), 7);
+        let engine = &config.engine;
+        assert_eq!(engine.rto_initial, Some(500));
+        assert_eq!(engine.max_retransmits, Some(5));
+        assert_eq!(engine.mss, Some(1400));
+        assert_eq!(engine.sack, Some(true));
+        assert_eq!(engine.time_wait, Some(2000));
+        assert_eq!(engine.fast_open, Some(false));
+        assert_eq!(engine.keepalive.unwrap().probes, Some(3));
+        assert_eq!(engine.arp_timeout, Some(60000));
+        assert_eq!(engine.client_pool, Some("192.168.222.2/32".parse().unwrap()));
+        assert_eq!(engine.check_client_pool(FlowSteeringMode::Port), Ok(()));
+        assert_eq!(config.routes.unwrap().len(), 1);
+        assert_eq!(config.default_gateway, Some(Ipv4Addr::new(192, 168, 222, 1)));
+    }
+
+    #[test]
+    fn timeouts_default_to_established() {
+        let timeouts = TcpTimeouts {
+            established: Some(10000),
+            syn_sent: Some(1000),
+            ..TcpTimeouts::default()
+        };
+        assert_eq!(timeouts.for_state(TcpState::SynSent), 1000);
+        assert_eq!(timeouts.for_state(TcpState::SynReceived), 10000);
+        assert_eq!(timeouts.for_state(TcpState::Listen), 10000);
+        assert_eq!(timeouts.for_state(TcpState::Established), 10000);
+        assert_eq!(timeouts.for_state(TcpState::LastAck), 10000);
+        assert_eq!(TcpTimeouts::default().for_state(TcpState::FinWait1), 200);
+        let timeouts = timeouts.with_established(500);
+        assert_eq!(timeouts.for_state(TcpState::Established), 500);
+        assert_eq!(timeouts.for_state(TcpState::CloseWait), 10000);
+    }
+
+    #[test]
+    fn limit_reduces_set_timeouts() {
+        let mut timeouts = TcpTimeouts {
+            established: Some(10000),
+            syn_sent: Some(1000),
+            ..TcpTimeouts::default()
+        };
+        assert!(timeouts.limit(5000));
+        assert_eq!(timeouts.established, Some(5000));
+        assert_eq!(timeouts.syn_sent, Some(1000));
+        assert_eq!(timeouts.fin_wait, None);
+        assert_eq!(timeouts.for_state(TcpState::Closing), 5000);
+        assert!(!timeouts.limit(5000));
+    }
+
+    fn configuration(routes: &str) -> Configuration {
+        let toml = format!(
+            "{}\nengine = {{ port = 999 }}\ntargets = [ {{ id = \"server 1\", ip = \"192.168.222.32\", port = 12345 }} ]\n",
+            routes
+        );
+        toml::from_str(&toml).unwrap()
+    }
+
+    #[test]
+    fn next_hop_is_gateway_of_longest_prefix() {
+        let config = configuration(
+            "default_gateway = \"192.168.222.1\"
+             routes = [ { prefix = \"10.0.0.0/8\", gateway = \"192.168.222.10\" },
+                        { prefix = \"10.1.0.0/16\", gateway = \"192.168.222.11\" },
+                        { prefix = \"192.168.222.0/24\" } ]",
+        );
+        let hop = |ip: &str| config.next_hop(ip.parse().unwrap()).to_string();
+        assert_eq!(hop("10.2.0.1"), "192.168.222.10");
+        assert_eq!(hop("10.1.0.1"), "192.168.222.11");
+        // directly connected
+        assert_eq!(hop("192.168.222.32"), "192.168.222.32");
+        assert_eq!(hop("172.16.0.1"), "192.168.222.1");
+    }
+
+    #[test]
+    fn next_hop_without_routes_is_target() {
+        let config = configuration("");
+        assert_eq!(config.targets[0].ip, Ipv4Addr::new(192, 168, 222, 32));
+        assert_eq!(config.next_hop(config.targets[0].ip), config.targets[0].ip);
+        let config = configuration("routes = [ { prefix = \"10.0.0.0/8\", gateway = \"192.168.222.10\" } ]");
+        assert_eq!(config.next_hop(Ipv4Addr::new(172, 16, 0, 1)), Ipv4Addr::new(172, 16, 0, 1));
+    }
+}
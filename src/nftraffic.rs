@@ -14,39 +14,84 @@ use bincode::{ deserialize};
 use separator::Separatable;
 
 use netfcts::tcp_common::{TcpState, TcpStatistics, TcpCounter, TcpRole, CData, L234Data, ReleaseCause, tcp_payload_size};
-use cmanager::{Connection, ConnectionManagerC, ConnectionManagerS};
+use cmanager::{Connection, ConnectionManagerC, ConnectionManagerS, EngineRecords, Keepalive, Reset};
+use retransmission::{RtoConfig, SEG_SYN, SEG_FIN, SEG_ACK, SEG_PSH, seq_gt, seq_lt};
+use statistics::{TcpExtStatistics, TcpExtCounter};
+use congestion::{CongestionControlType, DEFAULT_MSS};
+use tcp_options::{TcpOptionConfig, OptionBuf, FastOpenCookie, parse_tcp_options, set_tcp_options};
+use isn::IsnGenerator;
+use fastopen::{CookieGenerator, CookieCache};
+use arp::{ArpPacket, NeighbourTable, ETYPE_ARP, ARP_REQUEST, ARP_REPLY, ARP_RETRY_MILLIS};
 use ::{Configuration};
 #[cfg(feature = "profiling")]
 use netfcts::utils::TimeAdder;
 use {PipelineId, MessageFrom, MessageTo, TaskType};
-use netfcts::utils::Timeouts;
 use netfcts::tasks::{PRIVATE_ETYPE_PACKET, PRIVATE_ETYPE_TIMER, ETYPE_IPV4};
 use netfcts::tasks::{private_etype, PacketInjector, TickGenerator, install_task};
 use netfcts::timer_wheel::TimerWheel;
 use netfcts::{prepare_checksum_and_ttl, RunConfiguration};
 use netfcts::set_header;
-use netfcts::remove_tcp_options;
 use netfcts::{make_reply_packet, strip_payload};
-use netfcts::recstore::TEngineStore;
 
 use FnPayload;
 use std::convert::TryFrom;
+use std::cmp;
+use std::collections::VecDeque;
+use eui48::MacAddress;
 
 
 const MIN_FRAME_SIZE: usize = 60;
 
+/// codepoints of the ECN field in the IP header (RFC 3168, 5)
+const ECN_NOT_ECT: u8 = 0;
+const ECN_ECT0: u8 = 2;
+const ECN_CE: u8 = 3;
+
+/// challenge ACKs per second and pipeline (RFC 5961, 7), the former default of Linux
+const CHALLENGE_ACK_LIMIT: u32 = 1000;
+
 const TIMER_WHEEL_RESOLUTION_MS: u64 = 10;
 const TIMER_WHEEL_SLOTS: usize = 1002;
 const TIMER_WHEEL_SLOT_CAPACITY: usize = 2500;
-const SEQN_SHIFT: usize = 4;
+
+/// limits the challenge ACKs of a pipeline to CHALLENGE_ACK_LIMIT per second (RFC 5961, 7)
+struct ChallengeAckLimit {
+    cycles_per_second: u64,
+    second_start: u64,
+    sent: u32,
+}
+
+impl ChallengeAckLimit {
+    fn new(cpu_clock: u64) -> ChallengeAckLimit {
+        ChallengeAckLimit {
+            cycles_per_second: cpu_clock,
+            second_start: 0,
+            sent: 0,
+        }
+    }
+
+    /// true, if another challenge ACK may be sent at time now
+    fn permit(&mut self, now: u64) -> bool {
+        if now.wrapping_sub(self.second_start) >= self.cycles_per_second {
+            self.second_start = now;
+            self.sent = 0;
+        }
+        if self.sent < CHALLENGE_ACK_LIMIT {
+            self.sent += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
 
 pub fn setup_generator<FPL>(
     core: i32,
     pci: CacheAligned<PortQueueTxBuffered>,
     kni: CacheAligned<PortQueue>,
     sched: &mut StandaloneScheduler,
-    run_configuration: RunConfiguration<Configuration, TEngineStore>,
-    servers: Vec<L234Data>,
+    run_configuration: RunConfiguration<Configuration, EngineRecords>,
+    mut servers: Vec<L234Data>,
     f_set_payload: Box<FPL>,
 ) where
     FPL: FnPayload,
@@ -76,14 +121,35 @@ pub fn setup_generator<FPL>(
         pipeline_id.clone(),
         pci.port_queue.clone(),
         l4flow_for_this_core,
+        engine_config.client_pool,
         detailed_records,
     );
     let mut cm_s = ConnectionManagerS::new(detailed_records);
 
-    let mut timeouts = Timeouts::default_or_some(&engine_config.timeouts);
+    let mut timeouts = engine_config.timeouts();
     let max_open = engine_config.max_open.unwrap_or(cm_c.available_ports_count());
     let _fin_by_client = engine_config.fin_by_client.unwrap_or(1000);
     let fin_by_server = engine_config.fin_by_server.unwrap_or(1);
+    let rcv_wnd = engine_config.rcv_wnd();
+    let rst_via_kni = engine_config.rst_via_kni();
+    let nagle = engine_config.nagle();
+    let ecn = engine_config.ecn();
+    let option_config = TcpOptionConfig::new(
+        engine_config.mss(),
+        rcv_wnd,
+        engine_config.window_scaling(),
+        engine_config.sack(),
+        engine_config.timestamps(),
+        engine_config.fast_open(),
+    );
+    debug!("{} tcp options: {:?}", pipeline_id, option_config);
+    // congestion control per target, same index as servers
+    let congestion_controls: Vec<CongestionControlType> = run_configuration
+        .engine_configuration
+        .targets
+        .iter()
+        .map(|target| target.congestion_control.unwrap_or_default())
+        .collect();
 
     let mut wheel_c = TimerWheel::new(
         TIMER_WHEEL_SLOTS,
@@ -95,6 +161,23 @@ pub fn setup_generator<FPL>(
         system_data.cpu_clock * TIMER_WHEEL_RESOLUTION_MS / 1000,
         TIMER_WHEEL_SLOT_CAPACITY,
     );
+    // separate wheels for the retransmission timers
+    let mut wheel_rto_c = TimerWheel::new(
+        TIMER_WHEEL_SLOTS,
+        system_data.cpu_clock * TIMER_WHEEL_RESOLUTION_MS / 1000,
+        TIMER_WHEEL_SLOT_CAPACITY,
+    );
+    let mut wheel_rto_s = TimerWheel::new(
+        TIMER_WHEEL_SLOTS,
+        system_data.cpu_clock * TIMER_WHEEL_RESOLUTION_MS / 1000,
+        TIMER_WHEEL_SLOT_CAPACITY,
+    );
+    // delayed ACKs are only used by the client
+    let mut wheel_ack_c = TimerWheel::new(
+        TIMER_WHEEL_SLOTS,
+        system_data.cpu_clock * TIMER_WHEEL_RESOLUTION_MS / 1000,
+        TIMER_WHEEL_SLOT_CAPACITY,
+    );
     info!(
         "{} wheel cycle= {} millis, cpu-clock= {}",
         pipeline_id,
@@ -102,22 +185,66 @@ pub fn setup_generator<FPL>(
         system_data.cpu_clock,
     );
 
+    // with keepalive the idle time replaces the timeout of ESTABLISHED
+    if let Some(keepalive) = engine_config.keepalive {
+        timeouts = timeouts.with_established(keepalive.idle());
+    }
     // check that we do not overflow the wheel:
-    if timeouts.established.is_some() {
-        let timeout = timeouts.established.unwrap();
-        if timeout > wheel_c.get_max_timeout_cycles() - wheel_c.resolution() / 2 {
-            warn!(
-                "timeout defined in configuration file overflows timer wheel: reset to {} millis",
-                wheel_c.get_max_timeout_cycles() * 1000 / system_data.cpu_clock - TIMER_WHEEL_RESOLUTION_MS / 2
-            );
-            timeouts.established = Some(wheel_c.get_max_timeout_cycles() - wheel_c.resolution() / 2);
-        }
+    let timeout_max_millis = wheel_c.get_max_timeout_cycles() * 1000 / system_data.cpu_clock - TIMER_WHEEL_RESOLUTION_MS / 2;
+    if timeouts.limit(timeout_max_millis) {
+        warn!(
+            "timeout defined in configuration file overflows timer wheel: reset to {} millis",
+            timeout_max_millis
+        );
     }
+    debug!("{} timeouts: {:?}", pipeline_id, timeouts);
+    let keepalive = engine_config.keepalive.map(|k| Keepalive {
+        interval: cmp::min(k.interval(), timeout_max_millis) * system_data.cpu_clock / 1000,
+        probes: k.probes(),
+    });
+
+    // TIME_WAIT is limited by the timer wheel as well
+    let time_wait_max_millis = wheel_c.get_max_timeout_cycles() * 1000 / system_data.cpu_clock - TIMER_WHEEL_RESOLUTION_MS;
+    let time_wait = if engine_config.time_wait() > time_wait_max_millis {
+        warn!(
+            "time_wait defined in configuration file overflows timer wheel: reset to {} millis",
+            time_wait_max_millis
+        );
+        time_wait_max_millis * system_data.cpu_clock / 1000
+    } else {
+        engine_config.time_wait() * system_data.cpu_clock / 1000
+    };
+
+    // the back-off of the retransmission timer is limited by the timer wheel
+    let rto_max_millis = wheel_rto_c.get_max_timeout_cycles() * 1000 / system_data.cpu_clock - TIMER_WHEEL_RESOLUTION_MS;
+    let rto_config = RtoConfig::new(
+        system_data.cpu_clock,
+        cmp::min(engine_config.rto_initial(), rto_max_millis),
+        cmp::min(engine_config.rto_min(), rto_max_millis),
+        cmp::min(engine_config.rto_max(), rto_max_millis),
+        TIMER_WHEEL_RESOLUTION_MS,
+        engine_config.max_retransmits(),
+    );
+    debug!("{} retransmission: {:?}", pipeline_id, rto_config);
+
+    // delayed ACKs per target as (frequency, delay in cycles), same index as servers
+    let delayed_acks: Vec<(u16, u64)> = run_configuration
+        .engine_configuration
+        .targets
+        .iter()
+        .map(|target| match target.delayed_ack {
+            Some(delayed_ack) => (
+                delayed_ack.frequency(),
+                cmp::min(delayed_ack.timeout(), timeout_max_millis) * system_data.cpu_clock / 1000,
+            ),
+            None => (1, 0),
+        })
+        .collect();
 
 
     // setting up a a reverse message channel between this pipeline and the main program thread
     debug!("{} setting up reverse channel", pipeline_id);
-    let (remote_tx, rx) = channel::<MessageTo<TEngineStore>>();
+    let (remote_tx, rx) = channel::<MessageTo<EngineRecords>>();
     // we send the transmitter to the remote receiver of our messages
     tx.send(MessageFrom::Channel(pipeline_id.clone(), remote_tx)).unwrap();
 
@@ -132,6 +259,8 @@ pub fn setup_generator<FPL>(
     let mut start_stamp: u64 = 0;
     let mut stop_stamp: u64 = 0;
     let mut counter_s = TcpCounter::new();
+    let mut counter_xc = TcpExtCounter::new();
+    let mut counter_xs = TcpExtCounter::new();
 
     struct HoldingTime {
         // in cycles
@@ -152,13 +281,8 @@ pub fn setup_generator<FPL>(
         }
 
         #[inline]
-        fn add_hold(&mut self, start_low28bit: u32) {
-            let mut now = (utils::rdtscp_unsafe() & 0x000000000FFFFFFF) as u32;
-            if now <= start_low28bit {
-                // at least one overflow
-                now += 0x10000000;
-            }
-            let h = (now - start_low28bit) as u64;
+        fn add_hold(&mut self, start_stamp: u64) {
+            let h = utils::rdtscp_unsafe().saturating_sub(start_stamp);
             self.sum += h;
             self.count += 1;
             if h > self.max {
@@ -183,6 +307,28 @@ pub fn setup_generator<FPL>(
     }
 
     let mut hold = HoldingTime::new();
+    let isn_generator = IsnGenerator::new(system_data.cpu_clock);
+    let cookie_generator = CookieGenerator::new(engine_config.fast_open_secret.clone());
+    let mut cookie_cache = CookieCache::new(servers.len());
+    // targets without MAC are resolved with ARP, the requests are sent by the timer injector,
+    // for routed targets we resolve the gateway
+    let next_hops: Vec<u32> = servers
+        .iter()
+        .map(|server| u32::from(run_configuration.engine_configuration.next_hop(Ipv4Addr::from(server.ip))))
+        .collect();
+    let mut neighbours = NeighbourTable::new(
+        engine_config.neighbours.clone(),
+        &servers,
+        &next_hops,
+        engine_config.arp_timeout() * system_data.cpu_clock / 1000,
+        ARP_RETRY_MILLIS * system_data.cpu_clock / 1000,
+    );
+    let mut arp_ready: VecDeque<u32> = VecDeque::new();
+    // SYNs per target, which were sent to the next target, because its MAC was not yet resolved
+    let mut skipped_syns: Vec<usize> = vec![0; servers.len()];
+    let mut next_server = 0usize;
+    // shared by client and server, like the limit of a host
+    let mut challenge_ack_limit = ChallengeAckLimit::new(system_data.cpu_clock);
 
     #[cfg(feature = "profiling")]
     let mut rx_tx_stats = Vec::with_capacity(10000);
@@ -225,6 +371,24 @@ pub fn setup_generator<FPL>(
         .unwrap();
     let payload_injector_ready_flag = sched.get_ready_flag(&injector_uuid).unwrap();
 
+    // the timer injector provides the packets for segments which are triggered by timers, e.g. retransmissions
+    let (timer_producer, timer_consumer) = new_mpsc_queue_pair_with_size(64);
+    let injector_uuid = install_task(
+        sched,
+        "TimerInjector",
+        PacketInjector::new(
+            timer_producer,
+            &me,
+            0,
+            system_data.cpu_clock / engine_config.cps_limit() * 32,
+            3u16,
+        )
+        .set_start_delay(system_data.cpu_clock / 100),
+    );
+    tx.send(MessageFrom::Task(pipeline_id.clone(), injector_uuid, TaskType::TcpGenerator))
+        .unwrap();
+    let timer_injector_ready_flag = sched.get_ready_flag(&injector_uuid).unwrap();
+
     // set up the generator producing timer tick packets with our private EtherType
     let (producer_timerticks, consumer_timerticks) = new_mpsc_queue_pair();
     let tick_generator = TickGenerator::new(producer_timerticks, &me, system_data.cpu_clock / 100); // 10 ms
@@ -240,6 +404,7 @@ pub fn setup_generator<FPL>(
         vec![
             box syn_consumer,
             box payload_consumer,
+            box timer_consumer,
             box consumer_timerticks.set_urgent(),
             box receive_pci,
         ],
@@ -306,35 +471,201 @@ pub fn setup_generator<FPL>(
 
         let payload_injector_runs = || payload_injector_ready_flag.load(Ordering::SeqCst);
 
+        let timer_injector_start = || {
+            debug!("{} (re-)starting the timer injector at {}", thread_id, now());
+            timer_injector_ready_flag.store(true, Ordering::SeqCst);
+        };
+
+        let timer_injector_stop = || {
+            debug!("{}: stopping the timer injector at {}", thread_id, now());
+            timer_injector_ready_flag.store(false, Ordering::SeqCst);
+        };
+
+        let timer_injector_runs = || timer_injector_ready_flag.load(Ordering::SeqCst);
+
+        /// replaces the options of p, e.g. those of the DUT in a reply packet, with our options for c,
+        /// p acknowledges c.ackn_nxt, i.e. pending delayed ACKs are piggybacked, and echoes a CE mark with ECE,
+        /// p is not ECN-capable, unless mark_ect is applied for new data
         #[inline]
-        fn syn_received(p: &mut Pdu, c: &mut Connection) {
+        fn write_options(p: &mut Pdu, c: &mut Connection) {
+            set_tcp_options(p, OptionBuf::options(&c.options, &c.sack_blocks).as_slice());
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                if c.ece_pending() {
+                    tcp.set_ece_flag();
+                } else {
+                    tcp.unset_ece_flag();
+                }
+                tcp.unset_cwr_flag();
+            }
+            p.headers_mut().ip_mut(1).set_ecn(ECN_NOT_ECT);
+            c.ack_sent();
+        }
+
+        /// marks a segment with new data as ECN-capable, and signals a preceding reduction of the
+        /// congestion window with CWR (RFC 3168, 6.1.2)
+        #[inline]
+        fn mark_ect(p: &mut Pdu, c: &mut Connection) {
+            if c.ecn() && tcp_payload_size(p) > 0 {
+                p.headers_mut().ip_mut(1).set_ecn(ECN_ECT0);
+                if c.take_cwr() {
+                    p.headers_mut().tcp_mut(2).set_cwr_flag();
+                }
+            }
+        }
+
+        /// processes the ECN signals of a segment of the DUT (RFC 3168, 6.1): a CE mark is echoed with ECE until the
+        /// DUT answers with CWR, ECE reduces our congestion window
+        #[inline]
+        fn recv_ecn(p: &Pdu, c: &mut Connection, counter_x: &mut TcpExtCounter) {
+            if !c.ecn() {
+                return;
+            }
+            let tcp = p.headers().tcp(2);
+            if tcp.cwr_flag() {
+                c.cwr_received();
+            }
+            if p.headers().ip(1).ecn() == ECN_CE {
+                c.ce_received();
+                counter_x[TcpExtStatistics::RecvCe] += 1;
+            }
+            if tcp.ack_flag() && tcp.ece_flag() && !tcp.syn_flag() && c.ece_received(utils::rdtsc_unsafe()) {
+                counter_x[TcpExtStatistics::EcnWindowReduction] += 1;
+            }
+        }
+
+        /// remembers the TSval of an in-order segment of the DUT, which we echo (RFC 7323, 4.3)
+        #[inline]
+        fn update_ts_recent(p: &Pdu, c: &mut Connection) {
+            if c.options.timestamps {
+                if let Some((ts_val, _)) = parse_tcp_options(p).timestamp {
+                    c.options.ts_recent = ts_val;
+                }
+            }
+        }
+
+        /// replies with a SYN-ACK, which echoes data of the SYN, if it carries a valid TCP Fast Open cookie,
+        /// returns the size of the accepted data
+        #[inline]
+        fn syn_received(
+            p: &mut Pdu,
+            c: &mut Connection,
+            option_config: &TcpOptionConfig,
+            isn_generator: &IsnGenerator,
+            cookie_generator: &CookieGenerator,
+            counter_x: &mut TcpExtCounter,
+        ) -> usize {
             c.push_state(TcpState::SynReceived);
+            let now = utils::rdtsc_unsafe();
+            c.set_start_stamp(now);
             let client_ip = p.headers().ip(1).src();
+            let server_sock = (p.headers().ip(1).dst(), p.headers().tcp(2).dst_port());
+            let client_sock = (client_ip, p.headers().tcp(2).src_port());
+            c.peer_mac = p.headers().mac(0).src;
+            {
+                let tcp = p.headers().tcp(2);
+                c.irs = tcp.seq_num();
+                c.init_snd_wnd(tcp.seq_num(), 0, tcp.window_size() as u32);
+            }
+            let peer_options = parse_tcp_options(p);
+            c.options.negotiate(option_config, &peer_options);
+            let mut options = OptionBuf::syn_options(option_config, Some(&c.options));
+            let mut syn_data = 0;
+            if let (true, Some(cookie)) = (option_config.fast_open, peer_options.fast_open) {
+                let payload_sz = tcp_payload_size(p);
+                if cookie.is_request() {
+                    counter_x[TcpExtStatistics::FastOpenCookieRequest] += 1;
+                } else if payload_sz > 0 {
+                    counter_x[TcpExtStatistics::FastOpenSynData] += 1;
+                }
+                if !cookie.is_request() && cookie_generator.is_valid(client_ip, &cookie) {
+                    syn_data = payload_sz;
+                    if payload_sz > 0 {
+                        counter_x[TcpExtStatistics::FastOpenDataAcked] += 1;
+                    }
+                } else {
+                    if payload_sz > 0 {
+                        counter_x[TcpExtStatistics::FastOpenDataRejected] += 1;
+                    }
+                    // a requested cookie or a replacement for an invalid one (RFC 7413, 4.2.2)
+                    options.push_fast_open(&cookie_generator.cookie(client_ip));
+                }
+            }
+            if syn_data == 0 {
+                // without a valid cookie we acknowledge only the SYN
+                strip_payload(p);
+            }
             // debug!("checksum in = {:X}",p.get_header().checksum());
-            remove_tcp_options(p);
+            set_tcp_options(p, options.as_slice());
             make_reply_packet(p, 1);
-            //generate seq number:
-            c.seqn_nxt = (utils::rdtsc_unsafe() << 8) as u32;
+            c.seqn_nxt = isn_generator.isn(server_sock, client_sock, now);
+            c.seqn_una = c.seqn_nxt;
             {
                 let tcp = p.headers_mut().tcp_mut(2);
                 tcp.set_seq_num(c.seqn_nxt);
+                tcp.set_window_size(c.syn_window_field());
                 c.ackn_nxt = tcp.ack_num();
                 c.set_sock((client_ip, tcp.dst_port()));
+                // an ECN-setup SYN-ACK carries only ECE (RFC 3168, 6.1.1)
+                if c.ecn() {
+                    tcp.set_ece_flag();
+                } else {
+                    tcp.unset_ece_flag();
+                }
+                tcp.unset_cwr_flag();
             }
-            c.seqn_nxt = c.seqn_nxt.wrapping_add(1);
+            p.headers_mut().ip_mut(1).set_ecn(ECN_NOT_ECT);
+            c.push_unacked(c.seqn_nxt, SEG_SYN | SEG_ACK, &p.get_payload(2)[..syn_data], now);
+            c.seqn_nxt = c.seqn_nxt.wrapping_add(1 + syn_data as u32);
             prepare_checksum_and_ttl(p);
             //trace!("(SYN-)ACK to client, L3: { }, L4: { }", h.ip, h.tcp);
+            syn_data
+        }
+
+        /// answers a retransmitted SYN of the DUT with the SYN-ACK we have already sent, its data is left out,
+        /// if it does not fit into the mbuf, and follows with the retransmission of the segment
+        #[inline]
+        fn resend_synack(p: &mut Pdu, c: &mut Connection, option_config: &TcpOptionConfig, counter_x: &mut TcpExtCounter) {
+            // the data of a retransmitted SYN is replaced by the echo in our SYN-ACK, if we accepted it
+            strip_payload(p);
+            set_tcp_options(p, OptionBuf::syn_options(option_config, Some(&c.options)).as_slice());
+            make_reply_packet(p, 1);
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                tcp.set_seq_num(c.seqn_una);
+                tcp.set_ack_num(c.ackn_nxt);
+                tcp.set_window_size(c.syn_window_field());
+                if c.ecn() {
+                    tcp.set_ece_flag();
+                } else {
+                    tcp.unset_ece_flag();
+                }
+                tcp.unset_cwr_flag();
+            }
+            p.headers_mut().ip_mut(1).set_ecn(ECN_NOT_ECT);
+            if let Some(segment) = c.first_unacked_mut() {
+                // exclude the segment from RTT sampling
+                segment.retransmitted = true;
+                if !set_payload(p, &segment.payload) {
+                    counter_x[TcpExtStatistics::TailroomExceeded] += 1;
+                }
+            }
+            pad_frame(p);
+            prepare_checksum_and_ttl(p);
         }
 
         #[inline]
         fn synack_received(p: &mut Pdu, c: &mut Connection) {
             make_reply_packet(p, 1);
+            strip_payload(p);
             {
                 let tcp = p.headers_mut().tcp_mut(2);
                 c.ackn_nxt = tcp.ack_num();
                 tcp.unset_syn_flag();
                 tcp.set_seq_num(c.seqn_nxt);
+                tcp.set_window_size(c.window_field());
             }
+            write_options(p, c);
             prepare_checksum_and_ttl(p);
         }
 
@@ -344,14 +675,24 @@ pub fn setup_generator<FPL>(
             make_reply_packet(p, 0);
             {
                 let tcp = p.headers_mut().tcp_mut(2);
-                c.ackn_nxt = tcp.ack_num();
+                // c.ackn_nxt includes reassembled out-of-order payload
+                tcp.set_ack_num(c.ackn_nxt);
                 tcp.set_seq_num(c.seqn_nxt);
+                tcp.set_window_size(c.window_field());
                 tcp.unset_psh_flag();
                 if b_fin {
                     tcp.set_fin_flag();
                 }
             }
+            write_options(p, c);
+            mark_ect(p, c);
             let payload_sz = tcp_payload_size(p);
+            c.push_unacked(
+                c.seqn_nxt,
+                SEG_ACK | if b_fin { SEG_FIN } else { 0 },
+                &p.get_payload(2)[..payload_sz],
+                utils::rdtsc_unsafe(),
+            );
             c.seqn_nxt = c.seqn_nxt.wrapping_add(payload_sz as u32 + if b_fin { 1 } else { 0 });
             //if b_fin && h.tcp_payload_len()==0 { c.seqn_nxt = c.seqn_nxt.wrapping_add(1); }
             prepare_checksum_and_ttl(p);
@@ -363,64 +704,670 @@ pub fn setup_generator<FPL>(
             c: &mut Connection,
             me: &L234Data,
             servers: &Vec<L234Data>,
+            server_index: usize,
             pipeline_id: &PipelineId,
             syn_counter: &mut usize,
-        ) {
+            option_config: &TcpOptionConfig,
+            isn_generator: &IsnGenerator,
+            cookie_cache: &CookieCache,
+        ) -> bool {
             p.headers_mut().mac_mut(0).set_etype(0x0800); // overwrite private ethertype tag
-            c.set_server_index(*syn_counter as usize % servers.len());
-            set_header(&servers[c.server_index()], c.port(), p, &me.mac, me.ip);
-
-            //generate seq number:
-            c.seqn_nxt = (utils::rdtsc_unsafe() << SEQN_SHIFT) as u32;
+            c.set_server_index(server_index);
+            let server = &servers[c.server_index()];
+            let sock = c.sock().unwrap();
+            set_header(server, sock.1, p, &me.mac, sock.0);
+
+            let now = utils::rdtsc_unsafe();
+            c.set_start_stamp(now);
+            c.seqn_nxt = isn_generator.isn(sock, (server.ip, server.port), now);
+            c.seqn_una = c.seqn_nxt;
+            c.push_unacked(c.seqn_nxt, SEG_SYN, &[], now);
             {
                 let htcp = p.headers_mut().tcp_mut(2);
                 htcp.set_seq_num(c.seqn_nxt);
                 c.seqn_nxt = c.seqn_nxt.wrapping_add(1);
                 htcp.set_syn_flag();
-                htcp.set_window_size(5840); // 4* MSS(1460)
+                htcp.set_window_size(c.syn_window_field());
                 htcp.set_ack_num(0u32);
                 htcp.unset_ack_flag();
                 htcp.unset_psh_flag();
+                // an ECN-setup SYN carries ECE and CWR (RFC 3168, 6.1.1)
+                if c.ecn() {
+                    htcp.set_ece_flag();
+                    htcp.set_cwr_flag();
+                } else {
+                    htcp.unset_ece_flag();
+                    htcp.unset_cwr_flag();
+                }
             }
+            let mut options = OptionBuf::syn_options(option_config, None);
+            let mut b_cookie = false;
+            if option_config.fast_open {
+                match cookie_cache.get(c.server_index()) {
+                    Some(cookie) => {
+                        b_cookie = options.push_fast_open(cookie);
+                        if !b_cookie {
+                            debug!("{}: fast open cookie exceeds the option space", pipeline_id);
+                        }
+                    }
+                    None => {
+                        options.push_fast_open(&FastOpenCookie::default());
+                    }
+                }
+            }
+            set_tcp_options(p, options.as_slice());
             prepare_checksum_and_ttl(p);
 
             *syn_counter += 1;
             if *syn_counter % 1000 == 0 {
                 debug!("{}: sent {} SYNs", pipeline_id, *syn_counter);
             }
+            b_cookie
+        }
+
+        /// the next target in round robin order, whose MAC is resolved, unresolved targets are skipped
+        /// and the SYN is counted for them in skipped, returns None, if no target is resolved
+        #[inline]
+        fn select_server(servers: &Vec<L234Data>, next_server: &mut usize, skipped: &mut Vec<usize>) -> Option<usize> {
+            if servers.iter().all(|server| server.mac.is_nil()) {
+                return None;
+            }
+            loop {
+                let index = *next_server;
+                *next_server = (index + 1) % servers.len();
+                if !servers[index].mac.is_nil() {
+                    return Some(index);
+                }
+                skipped[index] += 1;
+            }
+        }
+
+        /// with a TCP Fast Open cookie in the SYN, our first payload of size payload_sz, which is already in p,
+        /// is sent with the SYN
+        #[inline]
+        fn add_syn_data(p: &mut Pdu, c: &mut Connection, payload_sz: usize) {
+            c.message_segment_sent(payload_sz);
+            c.inc_sent_payload_pkts();
+            if let Some(segment) = c.first_unacked_mut() {
+                segment.payload.extend_from_slice(&p.get_payload(2)[..payload_sz]);
+            }
+            c.seqn_nxt = c.seqn_nxt.wrapping_add(payload_sz as u32);
+            p.headers_mut().tcp_mut(2).unset_psh_flag();
+            pad_frame(p);
+            prepare_checksum_and_ttl(p);
         }
 
         #[inline]
         fn generate_fin(p: &mut Pdu, c: &mut Connection, me: &L234Data, servers: &Vec<L234Data>) {
             p.headers_mut().mac_mut(0).set_etype(0x0800); // overwrite private ethertype tag
-            set_header(&servers[c.server_index()], c.port(), p, &me.mac, me.ip);
+            let sock = c.sock().unwrap();
+            set_header(&servers[c.server_index()], sock.1, p, &me.mac, sock.0);
+            c.push_unacked(c.seqn_nxt, SEG_FIN | SEG_ACK, &[], utils::rdtsc_unsafe());
             {
                 let tcp = p.headers_mut().tcp_mut(2);
                 tcp.set_seq_num(c.seqn_nxt);
                 c.seqn_nxt = c.seqn_nxt.wrapping_add(1);
                 tcp.set_fin_flag();
                 tcp.unset_syn_flag();
-                tcp.set_window_size(5840); // 4* MSS(1460)
+                tcp.set_window_size(c.window_field());
                 tcp.set_ack_num(c.ackn_nxt);
                 tcp.set_ack_flag();
                 tcp.unset_psh_flag();
             }
+            write_options(p, c);
             prepare_checksum_and_ttl(p)
         }
 
         #[inline]
         fn prepare_payload_packet(c: &mut Connection, p: &mut Pdu, me: &L234Data, servers: &Vec<L234Data>) {
             p.headers_mut().mac_mut(0).set_etype(0x0800); // overwrite private ethertype tag
-            set_header(&servers[c.server_index()], c.port(), p, &me.mac, me.ip);
-            let tcp = p.headers_mut().tcp_mut(2);
-            tcp.set_seq_num(c.seqn_nxt);
-            tcp.unset_syn_flag();
-            tcp.set_window_size(5840); // 4* MSS(1460)
-            tcp.set_ack_num(c.ackn_nxt);
-            tcp.set_ack_flag();
-            tcp.set_psh_flag();
+            let sock = c.sock().unwrap();
+            set_header(&servers[c.server_index()], sock.1, p, &me.mac, sock.0);
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                tcp.set_seq_num(c.seqn_nxt);
+                tcp.unset_syn_flag();
+                tcp.set_window_size(c.window_field());
+                tcp.set_ack_num(c.ackn_nxt);
+                tcp.set_ack_flag();
+                tcp.set_psh_flag();
+            }
+            write_options(p, c);
+        }
+
+        /// replaces the payload of p with the next segment of the application message of c,
+        /// PSH is only set on the last segment, returns the size of the segment,
+        /// the segment is shortened, if it does not fit into the mbuf, the rest follows with the next segments
+        #[inline]
+        fn set_message_segment(p: &mut Pdu, c: &Connection, counter_x: &mut TcpExtCounter) -> usize {
+            let mut max_len = c.max_segment_size();
+            loop {
+                let (segment, b_last) = c.message_segment(max_len);
+                if set_payload(p, segment) {
+                    if b_last {
+                        p.headers_mut().tcp_mut(2).set_psh_flag();
+                    } else {
+                        p.headers_mut().tcp_mut(2).unset_psh_flag();
+                    }
+                    if max_len < c.max_segment_size() {
+                        counter_x[TcpExtStatistics::TailroomExceeded] += 1;
+                    }
+                    return segment.len();
+                }
+                max_len = segment.len() / 2;
+            }
+        }
+
+        /// replaces the payload of p, returns false and leaves p without payload, if the mbuf has not enough tail room
+        #[inline]
+        fn set_payload(p: &mut Pdu, payload: &[u8]) -> bool {
+            strip_payload(p);
+            if payload.is_empty() {
+                return true;
+            }
+            if p.add_to_payload_tail(payload.len()).is_err() {
+                return false;
+            }
+            let ip_sz = p.headers().ip(1).length();
+            p.headers_mut().ip_mut(1).set_length(ip_sz + payload.len() as u16);
+            p.copy_payload_from_u8_slice(payload, 2); // 2 -> tcp_payload
+            true
+        }
+
+        /// pads frames below the minimum Ethernet frame size, to be called when the headers and the payload are set
+        #[inline]
+        fn pad_frame(p: &mut Pdu) {
+            if p.data_len() < MIN_FRAME_SIZE {
+                let n_padding_bytes = MIN_FRAME_SIZE - p.data_len();
+                debug!("padding with {} 0x0 bytes", n_padding_bytes);
+                p.increase_payload_size(n_padding_bytes);
+            }
+        }
+
+        /// the next segment of reassembled payload of the DUT, which is handed to the payload logic,
+        /// it is counted as received payload
+        #[inline]
+        fn next_delivered(c: &mut Connection, counter: &mut TcpCounter) -> Option<Vec<u8>> {
+            let payload = c.pop_delivered();
+            if payload.is_some() {
+                counter[TcpStatistics::RecvPayload] += 1;
+                c.inc_recv_payload_pkts();
+            }
+            payload
+        }
+
+        /// echoes a segment of reassembled payload of the DUT from the timer injector, if b_fin is true, sets FIN flag,
+        /// returns false, if the payload does not fit into p
+        #[inline]
+        fn s_echo_delivered(
+            p: &mut Pdu,
+            c: &mut Connection,
+            src_sock: (u32, u16),
+            me: &L234Data,
+            peer: &L234Data,
+            payload: &[u8],
+            b_fin: bool,
+        ) -> bool {
+            p.headers_mut().mac_mut(0).set_etype(0x0800); // overwrite private ethertype tag
+            set_header(peer, src_sock.1, p, &me.mac, src_sock.0);
+            if !set_payload(p, payload) {
+                return false;
+            }
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                tcp.set_seq_num(c.seqn_nxt);
+                tcp.set_ack_num(c.ackn_nxt);
+                tcp.set_ack_flag();
+                tcp.unset_rst_flag();
+                tcp.unset_syn_flag();
+                tcp.unset_psh_flag();
+                if b_fin {
+                    tcp.set_fin_flag();
+                } else {
+                    tcp.unset_fin_flag();
+                }
+                tcp.set_window_size(c.window_field());
+            }
+            write_options(p, c);
+            mark_ect(p, c);
+            c.push_unacked(
+                c.seqn_nxt,
+                SEG_ACK | if b_fin { SEG_FIN } else { 0 },
+                payload,
+                utils::rdtsc_unsafe(),
+            );
+            c.seqn_nxt = c.seqn_nxt.wrapping_add(payload.len() as u32 + if b_fin { 1 } else { 0 });
+            pad_frame(p);
+            prepare_checksum_and_ttl(p);
+            true
+        }
+
+        /// replies in place with the payload of size payload_sz, which the callback has set in p
+        #[inline]
+        fn c_reply_with_payload(p: &mut Pdu, c: &mut Connection, payload_sz: usize) {
+            c.message_segment_sent(payload_sz);
+            c.inc_sent_payload_pkts();
+            p.headers_mut().tcp_mut(2).set_seq_num(c.seqn_nxt);
+            let flags = SEG_ACK | if p.headers().tcp(2).psh_flag() { SEG_PSH } else { 0 };
+            c.push_unacked(c.seqn_nxt, flags, &p.get_payload(2)[..payload_sz], utils::rdtsc_unsafe());
+            c.seqn_nxt = c.seqn_nxt.wrapping_add(payload_sz as u32);
+            make_reply_packet(p, 0);
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                tcp.set_ack_num(c.ackn_nxt);
+                tcp.set_window_size(c.window_field());
+                tcp.unset_syn_flag();
+                tcp.unset_fin_flag();
+            }
+            write_options(p, c);
+            mark_ect(p, c);
+            pad_frame(p);
+            prepare_checksum_and_ttl(p);
+        }
+
+        /// holds back a payload segment of len bytes, if it exceeds the usable window, or with Nagle's algorithm
+        /// (RFC 896), if it is smaller than a full segment while our data is not yet acknowledged,
+        /// returns true if the segment is held back
+        #[inline]
+        fn hold_back(c: &mut Connection, len: usize, nagle: bool, counter_x: &mut TcpExtCounter) -> bool {
+            if len as u32 > c.usable_window() {
+                counter_x[TcpExtStatistics::SendWindowFull] += 1;
+            } else if nagle && len < c.max_segment_size() && c.has_unacked() {
+                counter_x[TcpExtStatistics::NagleDelayed] += 1;
+            } else {
+                return false;
+            }
+            c.set_wnd_blocked(true);
+            true
+        }
+
+        /// queues out-of-order payload of the DUT for reassembly and acknowledges it immediately (RFC 5681, 4.2),
+        /// if SACK is permitted, queued payload is reported in a SACK block, segments with FIN are not queued,
+        /// returns true if p is turned into the duplicate ACK
+        #[inline]
+        fn recv_out_of_order(p: &mut Pdu, c: &mut Connection, counter_x: &mut TcpExtCounter) -> bool {
+            let payload_sz = tcp_payload_size(p);
+            if payload_sz == 0 || p.headers().tcp(2).rst_flag() {
+                return false;
+            }
+            let seqn = p.headers().tcp(2).seq_num();
+            if !p.headers().tcp(2).fin_flag() && c.queue_out_of_order(seqn, &p.get_payload(2)[..payload_sz]) {
+                counter_x[TcpExtStatistics::RecvOutOfOrder] += 1;
+                if c.options.sack_permitted {
+                    c.sack_blocks.insert(seqn, seqn.wrapping_add(payload_sz as u32));
+                    counter_x[TcpExtStatistics::SentSack] += 1;
+                }
+            }
+            send_dup_ack(p, c);
+            true
+        }
+
+        /// marks our segments which the DUT reports in its SACK option
+        #[inline]
+        fn recv_sack(p: &Pdu, c: &mut Connection, counter_x: &mut TcpExtCounter) {
+            if c.options.sack_permitted && c.has_unacked() {
+                let options = parse_tcp_options(p);
+                if !options.sack.is_empty() {
+                    counter_x[TcpExtStatistics::RecvSack] += 1;
+                    c.sack_unacked(&options.sack);
+                }
+            }
+        }
+
+        /// the DUT as peer of a server side connection, for the packets which the timers inject
+        #[inline]
+        fn server_peer(mac: MacAddress, sock: (u32, u16)) -> L234Data {
+            L234Data {
+                mac,
+                ip: sock.0,
+                port: sock.1,
+                server_id: String::new(),
+                index: 0,
+            }
+        }
+
+        /// rebuilds the oldest unacknowledged segment of c, which is not reported by a SACK block, in the injected packet p,
+        /// returns false, if the payload of the segment does not fit into p, the segment is then retried by the next
+        /// retransmission timeout
+        #[inline]
+        fn retransmit_segment(
+            p: &mut Pdu,
+            c: &mut Connection,
+            src_sock: (u32, u16),
+            me: &L234Data,
+            peer: &L234Data,
+            option_config: &TcpOptionConfig,
+        ) -> bool {
+            p.headers_mut().mac_mut(0).set_etype(0x0800); // overwrite private ethertype tag
+            set_header(peer, src_sock.1, p, &me.mac, src_sock.0);
+            let ackn_nxt = c.ackn_nxt;
+            let flags = c.first_unsacked_mut().unwrap().flags;
+            let (window, options) = if flags & SEG_SYN == 0 {
+                (c.window_field(), OptionBuf::options(&c.options, &c.sack_blocks))
+            } else if flags & SEG_ACK == 0 {
+                (c.syn_window_field(), OptionBuf::syn_options(option_config, None))
+            } else {
+                (c.syn_window_field(), OptionBuf::syn_options(option_config, Some(&c.options)))
+            };
+            if flags & SEG_ACK != 0 {
+                c.ack_sent();
+            }
+            // retransmissions are not ECN-capable (RFC 3168, 6.1.5), a SYN or SYN-ACK repeats the ECN setup
+            let (ece, cwr) = if flags & SEG_SYN != 0 {
+                (c.ecn(), c.ecn() && flags & SEG_ACK == 0)
+            } else {
+                (c.ece_pending(), false)
+            };
+            p.headers_mut().ip_mut(1).set_ecn(ECN_NOT_ECT);
+            let segment = c.first_unsacked_mut().unwrap();
+            segment.retransmitted = true;
+            segment.stamp = utils::rdtsc_unsafe();
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                tcp.set_seq_num(segment.seqn);
+                tcp.set_window_size(window);
+                if segment.flags & SEG_SYN != 0 {
+                    tcp.set_syn_flag();
+                } else {
+                    tcp.unset_syn_flag();
+                }
+                if segment.flags & SEG_FIN != 0 {
+                    tcp.set_fin_flag();
+                } else {
+                    tcp.unset_fin_flag();
+                }
+                if segment.flags & SEG_PSH != 0 {
+                    tcp.set_psh_flag();
+                } else {
+                    tcp.unset_psh_flag();
+                }
+                if segment.flags & SEG_ACK != 0 {
+                    tcp.set_ack_num(ackn_nxt);
+                    tcp.set_ack_flag();
+                } else {
+                    tcp.set_ack_num(0u32);
+                    tcp.unset_ack_flag();
+                }
+                if ece {
+                    tcp.set_ece_flag();
+                } else {
+                    tcp.unset_ece_flag();
+                }
+                if cwr {
+                    tcp.set_cwr_flag();
+                } else {
+                    tcp.unset_cwr_flag();
+                }
+            }
+            set_tcp_options(p, options.as_slice());
+            if !set_payload(p, &segment.payload) {
+                return false;
+            }
+            pad_frame(p);
+            prepare_checksum_and_ttl(p);
+            true
+        }
+
+        /// learns the MAC of targets from ARP packets and answers ARP requests for our address and the addresses
+        /// of the client pool in place, returns the group index for p, everything else goes to the KNI i/f
+        #[inline]
+        fn recv_arp(p: &mut Pdu, me: &L234Data, cm_c: &ConnectionManagerC, neighbours: &NeighbourTable) -> usize {
+            let arp = match ArpPacket::parse(p.get_payload(0)) {
+                Some(arp) => arp,
+                None => return 2,
+            };
+            neighbours.learn(&arp, utils::rdtsc_unsafe());
+            if arp.op != ARP_REQUEST || arp.tpa != me.ip && !cm_c.owns_ip(arp.tpa) {
+                return 2;
+            }
+            ArpPacket {
+                op: ARP_REPLY,
+                sha: me.mac,
+                spa: arp.tpa,
+                tha: arp.sha,
+                tpa: arp.spa,
+            }
+            .write(p.get_payload_mut(0));
+            let mac = p.headers_mut().mac_mut(0);
+            mac.set_dmac(&arp.sha);
+            mac.set_smac(&me.mac);
+            1
+        }
+
+        /// turns the injected packet into a broadcast ARP request for ip
+        #[inline]
+        fn generate_arp_request(p: &mut Pdu, ip: u32, me: &L234Data) {
+            {
+                let mac = p.headers_mut().mac_mut(0);
+                mac.set_etype(ETYPE_ARP); // overwrite private ethertype tag
+                mac.set_dmac(&MacAddress::broadcast());
+                mac.set_smac(&me.mac);
+            }
+            ArpPacket {
+                op: ARP_REQUEST,
+                sha: me.mac,
+                spa: me.ip,
+                tha: MacAddress::nil(),
+                tpa: ip,
+            }
+            .write(p.get_payload_mut(0));
+        }
+
+        /// aborts a timed out connection, the RST carries our next seqn (RFC 793, p. 62)
+        #[inline]
+        fn generate_rst(p: &mut Pdu, reset: &Reset, src_sock: (u32, u16), me: &L234Data, peer: &L234Data) {
+            p.headers_mut().mac_mut(0).set_etype(0x0800); // overwrite private ethertype tag
+            set_header(peer, src_sock.1, p, &me.mac, src_sock.0);
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                tcp.set_seq_num(reset.seqn);
+                tcp.set_rst_flag();
+                tcp.unset_syn_flag();
+                tcp.unset_fin_flag();
+                tcp.unset_psh_flag();
+                tcp.set_window_size(0);
+                if let Some(ackn) = reset.ackn {
+                    tcp.set_ack_num(ackn);
+                    tcp.set_ack_flag();
+                } else {
+                    tcp.set_ack_num(0u32);
+                    tcp.unset_ack_flag();
+                }
+            }
+            set_tcp_options(p, &[]);
+            prepare_checksum_and_ttl(p);
+        }
+
+        /// sends a delayed ACK or, with seqn one below seqn_una, a keepalive or window probe from the timer injector
+        #[inline]
+        fn generate_ack(p: &mut Pdu, c: &mut Connection, seqn: u32, src_sock: (u32, u16), me: &L234Data, peer: &L234Data) {
+            p.headers_mut().mac_mut(0).set_etype(0x0800); // overwrite private ethertype tag
+            set_header(peer, src_sock.1, p, &me.mac, src_sock.0);
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                tcp.set_seq_num(seqn);
+                tcp.set_ack_num(c.ackn_nxt);
+                tcp.set_ack_flag();
+                tcp.unset_rst_flag();
+                tcp.unset_syn_flag();
+                tcp.unset_fin_flag();
+                tcp.unset_psh_flag();
+                tcp.set_window_size(c.window_field());
+            }
+            write_options(p, c);
+            prepare_checksum_and_ttl(p);
+        }
+
+        /// acknowledges in-order payload of the DUT with a pure ACK, unless the ACK is delayed,
+        /// returns the group index for p
+        #[inline]
+        fn ack_payload(
+            p: &mut Pdu,
+            c: &mut Connection,
+            wheel_ack: &mut TimerWheel<(u32, u16)>,
+            counter_x: &mut TcpExtCounter,
+        ) -> usize {
+            let sock = c.sock().unwrap();
+            if c.delay_ack(wheel_ack, sock) {
+                0
+            } else {
+                send_dup_ack(p, c);
+                counter_x[TcpExtStatistics::SentPureAck] += 1;
+                1
+            }
         }
 
+        /// answers a segment, for which we have no connection, with a RST (RFC 793, p. 36)
+        #[inline]
+        fn reply_with_rst(p: &mut Pdu) {
+            let (b_ack, ackn, seq_inc) = {
+                let tcp = p.headers().tcp(2);
+                let seq_inc = if tcp.syn_flag() { 1 } else { 0 } + if tcp.fin_flag() { 1 } else { 0 };
+                (tcp.ack_flag(), tcp.ack_num(), seq_inc)
+            };
+            // sets the ackn to SEG.SEQ + SEG.LEN
+            make_reply_packet(p, seq_inc);
+            strip_payload(p);
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                if b_ack {
+                    tcp.set_seq_num(ackn);
+                    tcp.set_ack_num(0u32);
+                    tcp.unset_ack_flag();
+                } else {
+                    tcp.set_seq_num(0u32);
+                    tcp.set_ack_flag();
+                }
+                tcp.set_rst_flag();
+                tcp.unset_syn_flag();
+                tcp.unset_fin_flag();
+                tcp.unset_psh_flag();
+                tcp.unset_ece_flag();
+                tcp.unset_cwr_flag();
+                tcp.set_window_size(0);
+            }
+            p.headers_mut().ip_mut(1).set_ecn(ECN_NOT_ECT);
+            set_tcp_options(p, &[]);
+            prepare_checksum_and_ttl(p);
+        }
+
+        /// rejects a segment, which does not belong to a connection, either natively with a RST,
+        /// or by forwarding it to the KNI i/f, whose kernel sends the RST, returns the group index for p
+        #[inline]
+        fn reject_segment(p: &mut Pdu, rst_via_kni: bool, counter_x: &mut TcpExtCounter) -> usize {
+            if rst_via_kni {
+                counter_x[TcpExtStatistics::ForwardedToKni] += 1;
+                2
+            } else if p.headers().tcp(2).rst_flag() {
+                // a RST is never answered
+                0
+            } else {
+                reply_with_rst(p);
+                counter_x[TcpExtStatistics::SentRstNoConnection] += 1;
+                1
+            }
+        }
+
+        /// states after the three-way handshake, in which RSTs and SYNs are challenged (RFC 5961)
+        #[inline]
+        fn synchronized_state(state: TcpState) -> bool {
+            match state {
+                TcpState::Listen | TcpState::SynSent | TcpState::SynReceived => false,
+                _ => true,
+            }
+        }
+
+        /// a RST within the receive window, in SYN-RECEIVED it resets the connection (RFC 793)
+        #[inline]
+        fn rst_in_window(p: &Pdu, c: &Connection) -> bool {
+            let seqn = p.headers().tcp(2).seq_num();
+            p.headers().tcp(2).rst_flag()
+                && !seq_lt(seqn, c.ackn_nxt)
+                && seq_lt(seqn, c.ackn_nxt.wrapping_add(cmp::max(c.rcv_wnd, 1)))
+        }
+
+        /// validates RSTs and SYNs of the DUT in a synchronized state (RFC 5961), returns None, if the segment is
+        /// acceptable, otherwise the group index for p, which is either a challenge ACK or dropped
+        #[inline]
+        fn challenge_segment(
+            p: &mut Pdu,
+            c: &mut Connection,
+            limit: &mut ChallengeAckLimit,
+            counter_x: &mut TcpExtCounter,
+        ) -> Option<usize> {
+            let (seqn, b_rst, b_syn) = {
+                let tcp = p.headers().tcp(2);
+                (tcp.seq_num(), tcp.rst_flag(), tcp.syn_flag())
+            };
+            let statistic = if b_rst {
+                if seqn == c.ackn_nxt {
+                    return None;
+                } else if seq_gt(seqn, c.ackn_nxt) && seq_lt(seqn, c.ackn_nxt.wrapping_add(c.rcv_wnd)) {
+                    TcpExtStatistics::SentChallengeAckRst
+                } else {
+                    counter_x[TcpExtStatistics::DroppedRstOutOfWindow] += 1;
+                    return Some(0);
+                }
+            } else if b_syn && seqn != c.irs {
+                // a retransmission of the initial SYN of the DUT is handled by the sequence number check
+                TcpExtStatistics::SentChallengeAckSyn
+            } else {
+                return None;
+            };
+            if limit.permit(utils::rdtsc_unsafe()) {
+                send_dup_ack(p, c);
+                counter_x[statistic] += 1;
+                Some(1)
+            } else {
+                counter_x[TcpExtStatistics::ChallengeAckLimited] += 1;
+                Some(0)
+            }
+        }
+
+        /// a duplicate ACK according to RFC 5681, must be checked before the send window is updated
+        #[inline]
+        fn is_dup_ack(p: &Pdu, c: &Connection) -> bool {
+            let tcp = p.headers().tcp(2);
+            tcp.ack_num() == c.seqn_una
+                && c.has_unacked()
+                && tcp_payload_size(p) == 0
+                && !tcp.syn_flag()
+                && !tcp.fin_flag()
+                && c.scaled_window(tcp.window_size()) == c.snd_wnd
+        }
+
+        /// re-acknowledges a segment which we have already received, e.g. a retransmission of the DUT
+        #[inline]
+        fn send_dup_ack(p: &mut Pdu, c: &mut Connection) {
+            make_reply_packet(p, 0);
+            strip_payload(p);
+            {
+                let tcp = p.headers_mut().tcp_mut(2);
+                tcp.unset_syn_flag();
+                tcp.unset_fin_flag();
+                tcp.unset_psh_flag();
+                tcp.set_ack_flag();
+                tcp.set_seq_num(c.seqn_nxt);
+                tcp.set_ack_num(c.ackn_nxt);
+                tcp.set_window_size(c.window_field());
+            }
+            write_options(p, c);
+            prepare_checksum_and_ttl(p);
+        }
+
+        /// states in which data may flow in at least one direction
+        #[inline]
+        fn transfer_state(state: TcpState) -> bool {
+            state == TcpState::Established || state == TcpState::CloseWait || state == TcpState::FinWait2
+        }
+
+        /// the ackn which acknowledges the FIN of the DUT in p and its payload
+        #[inline]
+        fn ackn_for_fin(p: &Pdu) -> u32 {
+            p.headers().tcp(2).seq_num().wrapping_add(tcp_payload_size(p) as u32 + 1)
+        }
+
+        /// replies to the FIN of the DUT with our FIN, c.ackn_nxt must already acknowledge the FIN of the DUT
         #[inline]
         fn passive_close(p: &mut Pdu, c: &mut Connection, thread_id: &String, counter: &mut TcpCounter) {
             debug!(
@@ -436,12 +1383,15 @@ pub fn setup_generator<FPL>(
             make_reply_packet(p, 1);
             {
                 let tcp = p.headers_mut().tcp_mut(2);
-                c.ackn_nxt = tcp.ack_num();
+                tcp.set_ack_num(c.ackn_nxt);
                 tcp.set_ack_flag();
                 tcp.set_seq_num(c.seqn_nxt);
+                tcp.set_window_size(c.window_field());
             }
+            c.push_unacked(c.seqn_nxt, SEG_FIN | SEG_ACK, &[], utils::rdtsc_unsafe());
             c.seqn_nxt = c.seqn_nxt.wrapping_add(1);
             strip_payload(p);
+            write_options(p, c);
             prepare_checksum_and_ttl(p);
             counter[TcpStatistics::SentFinPssv] += 1;
             counter[TcpStatistics::SentAck4Fin] += 1;
@@ -489,8 +1439,10 @@ pub fn setup_generator<FPL>(
                 tcp.set_ack_flag();
                 c.ackn_nxt = tcp.ack_num();
                 tcp.set_seq_num(c.seqn_nxt);
+                tcp.set_window_size(c.window_field());
             }
             strip_payload(p);
+            write_options(p, c);
             prepare_checksum_and_ttl(p);
             counter[TcpStatistics::SentAck4Fin] += 1;
             tcp_closed
@@ -509,7 +1461,7 @@ pub fn setup_generator<FPL>(
             if *counter == nr_connections {
                 *stop_stamp = utils::rdtscp_unsafe()
             }
-            hold.add_hold(c.seqn_nxt >> SEQN_SHIFT);
+            hold.add_hold(c.start_stamp());
         }
 
         // *****  the closure starts here with processing
@@ -517,28 +1469,82 @@ pub fn setup_generator<FPL>(
         #[cfg(feature = "profiling")]
         let timestamp_entry = utils::rdtsc_unsafe();
 
-        let c_recv_payload = |p: &mut Pdu, c: &mut Connection| {
+        // replies to payload of the DUT with the next payload, or with a FIN,
+        // if the send window is exhausted, we only acknowledge and defer the payload to the payload injector
+        let c_recv_payload = |p: &mut Pdu,
+                              c: &mut Connection,
+                              counter: &mut TcpCounter,
+                              counter_x: &mut TcpExtCounter,
+                              wheel_ack: &mut TimerWheel<(u32, u16)>|
+         -> usize {
             let mut b_fin = false;
+            if c.wnd_blocked() || c.message_pending() {
+                // a deferred payload segment is still waiting for the window to open,
+                // or the payload injector is sending the segments of a message
+                return ack_payload(p, c, wheel_ack, counter_x);
+            }
             f_set_payload(p, c, None, &mut b_fin);
             if !b_fin {
-                c.inc_sent_payload_pkts();
-                p.headers_mut().tcp_mut(2).set_seq_num(c.seqn_nxt);
-                let payload_sz = tcp_payload_size(p);
-                c.seqn_nxt = c.seqn_nxt.wrapping_add(payload_sz as u32);
-                make_reply_packet(p, 0);
-                p.headers_mut().tcp_mut(2).set_ack_num(c.ackn_nxt);
-                prepare_checksum_and_ttl(p);
+                let payload_sz = if c.message_pending() {
+                    set_message_segment(p, c, counter_x)
+                } else {
+                    tcp_payload_size(p)
+                };
+                if hold_back(c, payload_sz, nagle, counter_x) {
+                    return ack_payload(p, c, wheel_ack, counter_x);
+                }
+                c_reply_with_payload(p, c, payload_sz);
+                counter[TcpStatistics::SentPayload] += 1;
             } else {
                 generate_fin(p, c, &me, &servers);
                 c.set_release_cause(ReleaseCause::ActiveClose);
                 c.push_state(TcpState::FinWait1);
+                counter[TcpStatistics::SentFin] += 1;
+            }
+            1
+        };
+
+        // the DUT closed its direction (half-close), in CLOSE-WAIT the callback may still send payload,
+        // we acknowledge the FIN with this payload, or with our FIN, if the callback sets b_fin or no payload
+        let c_recv_fin = |p: &mut Pdu,
+                          c: &mut Connection,
+                          counter: &mut TcpCounter,
+                          counter_x: &mut TcpExtCounter|
+         -> usize {
+            c.ackn_nxt = ackn_for_fin(p);
+            c.push_state(TcpState::CloseWait);
+            let mut b_fin = false;
+            let mut payload_sz = 0;
+            if !c.wnd_blocked() && !c.message_pending() {
+                payload_sz = f_set_payload(p, c, None, &mut b_fin);
+                if !b_fin && c.message_pending() {
+                    payload_sz = set_message_segment(p, c, counter_x);
+                }
+                if payload_sz == 0 {
+                    b_fin = true;
+                }
+            }
+            if b_fin {
+                passive_close(p, c, &thread_id, counter);
+                return 1;
             }
-            b_fin
+            counter[TcpStatistics::RecvFin] += 1;
+            counter[TcpStatistics::SentAck4Fin] += 1;
+            c.set_release_cause(ReleaseCause::PassiveClose);
+            if payload_sz == 0 || hold_back(c, payload_sz, nagle, counter_x) {
+                // our deferred payload is sent by the payload injector
+                send_dup_ack(p, c);
+            } else {
+                c_reply_with_payload(p, c, payload_sz);
+                counter[TcpStatistics::SentPayload] += 1;
+            }
+            1
         };
 
         let pipeline_ip = cm_c.ip();
         let tcp_port_base = cm_c.tcp_port_base();
         let b_private_etype;
+        let mut b_arp = false;
         {
             let mac_header = pdu.headers().mac(0);
             b_private_etype = private_etype(&mac_header.etype());
@@ -547,18 +1553,25 @@ pub fn setup_generator<FPL>(
                     debug!("{} from pci: discarding because mac unknown: {} ", thread_id, mac_header);
                     return 0;
                 }
-                if mac_header.etype() != 0x0800 && !b_private_etype {
-                    // everything other than Ipv4 or our own packets we send to KNI, i.e. group 2
+                if mac_header.etype() == ETYPE_ARP {
+                    b_arp = true;
+                } else if mac_header.etype() != 0x0800 && !b_private_etype {
+                    // everything other than Ipv4, ARP or our own packets we send to KNI, i.e. group 2
                     return 2;
                 }
             }
         }
+        if b_arp {
+            return recv_arp(pdu, &me, &cm_c, &neighbours);
+        }
 
         {
             let ip_header = pdu.headers().ip(1);
             if !b_private_etype {
                 // everything other than TCP, and everything not addressed to us we send to KNI, i.e. group 2
-                if ip_header.protocol() != 6 || ip_header.dst() != pipeline_ip && ip_header.dst() != me.ip {
+                if ip_header.protocol() != 6
+                    || ip_header.dst() != pipeline_ip && ip_header.dst() != me.ip && !cm_c.owns_ip(ip_header.dst())
+                {
                     return 2;
                 }
             }
@@ -583,7 +1596,12 @@ pub fn setup_generator<FPL>(
         // this is cumbersome, but we must make the  borrow checker happy
         let mut b_release_connection_c = false;
         let mut b_release_connection_s = false;
+        // server connection with reassembled payload, which the timer injector echoes
+        let mut b_delivery_s = false;
+        // actively closed client connection enters or restarts TIME_WAIT
+        let mut b_time_wait_c = false;
         let mut ready_connection = None;
+        let mut fast_rtx_connection = None;
         let server_listen_port = cm_c.listen_port();
 
         let nr_connections = run_configuration.engine_configuration.test_size.unwrap_or(128);
@@ -597,19 +1615,67 @@ pub fn setup_generator<FPL>(
                 if counter_c[TcpStatistics::SentSyn] < nr_connections {
                     //info!("syn= {}, ack= {}, open= {}", counter_c[TcpStatistics::SentSyn], counter_c[TcpStatistics::RecvSynAck], cm_c.concurrent_connections());
                     //assert!(counter_c[TcpStatistics::SentSyn]- counter_c[TcpStatistics::RecvSynAck] <= max_open);
-                    if cm_c.concurrent_connections() < max_open {
+                    // we wait until a port leaves TIME_WAIT, and until at least one target is resolved with ARP
+                    let opt_server = if cm_c.concurrent_connections() < max_open && cm_c.has_free_ports() {
+                        select_server(&servers, &mut next_server, &mut skipped_syns)
+                    } else {
+                        None
+                    };
+                    if let Some(server_index) = opt_server {
                         if let Some(c) = cm_c.create(TcpRole::Client) {
-                            generate_syn(
+                            c.rcv_wnd = rcv_wnd;
+                            c.set_ecn(ecn);
+                            let b_cookie = generate_syn(
                                 pdu,
                                 c,
                                 &me,
                                 &servers,
+                                server_index,
                                 &pipeline_id_clone,
                                 &mut counter_c[TcpStatistics::SentSyn],
+                                &option_config,
+                                &isn_generator,
+                                &cookie_cache,
                             );
+                            if b_cookie {
+                                let cdata = CData::new(
+                                    SocketAddrV4::new(Ipv4Addr::from(pipeline_ip), server_listen_port),
+                                    c.port(),
+                                    c.uid(),
+                                );
+                                let mut b_fin = false;
+                                f_set_payload(pdu, c, Some(cdata), &mut b_fin);
+                                let payload_sz = if !b_fin && c.message_pending() {
+                                    set_message_segment(pdu, c, &mut counter_xc)
+                                } else {
+                                    tcp_payload_size(pdu)
+                                };
+                                if !b_fin && payload_sz > 0 {
+                                    add_syn_data(pdu, c, payload_sz);
+                                    counter_c[TcpStatistics::SentPayload] += 1;
+                                    counter_xc[TcpExtStatistics::FastOpenSynData] += 1;
+                                } else {
+                                    // nothing to send with the SYN, the callback is asked again after the handshake
+                                    strip_payload(pdu);
+                                    prepare_checksum_and_ttl(pdu);
+                                }
+                            } else if option_config.fast_open && cookie_cache.get(c.server_index()).is_none() {
+                                counter_xc[TcpExtStatistics::FastOpenCookieRequest] += 1;
+                            }
                             c.push_state(TcpState::SynSent);
-                            c.wheel_slot_and_index =
-                                wheel_c.schedule(&(timeouts.established.unwrap() * system_data.cpu_clock / 1000), c.port());
+                            c.set_congestion_control(
+                                congestion_controls[c.server_index()],
+                                DEFAULT_MSS,
+                                system_data.cpu_clock,
+                            );
+                            let (ack_frequency, ack_delay) = delayed_acks[c.server_index()];
+                            c.set_delayed_ack(ack_frequency, ack_delay);
+                            c.wheel_slot_and_index = wheel_c.schedule(
+                                &(timeouts.syn_sent() * system_data.cpu_clock / 1000),
+                                c.sock().unwrap(),
+                            );
+                            c.init_rtt(&rto_config);
+                            c.start_rto(&mut wheel_rto_c, c.sock().unwrap());
                             group_index = 1;
                             #[cfg(feature = "profiling")]
                             time_adders[4].add_diff(utils::rdtsc_unsafe() - timestamp_entry);
@@ -629,9 +1695,39 @@ pub fn setup_generator<FPL>(
                 if let Some(c) = cm_c.get_ready_connection() {
                     prepare_payload_packet(c, pdu, &me, &servers);
                     let mut b_fin = false;
-                    cdata.client_port = c.port();
-                    cdata.uuid = c.uid();
-                    f_set_payload(pdu, c, Some(cdata), &mut b_fin);
+                    if !c.message_pending() {
+                        match next_delivered(c, &mut counter_c) {
+                            Some(payload) => {
+                                // the callback answers reassembled payload of the DUT like in-order payload
+                                if !set_payload(pdu, &payload) {
+                                    counter_xc[TcpExtStatistics::TailroomExceeded] += 1;
+                                }
+                                f_set_payload(pdu, c, None, &mut b_fin);
+                            }
+                            None => {
+                                cdata.client_port = c.port();
+                                cdata.uuid = c.uid();
+                                f_set_payload(pdu, c, Some(cdata), &mut b_fin);
+                            }
+                        }
+                    }
+                    let payload_sz = if !b_fin && c.message_pending() {
+                        set_message_segment(pdu, c, &mut counter_xc)
+                    } else {
+                        tcp_payload_size(pdu)
+                    };
+                    if payload_sz == 0 && c.state() == TcpState::CloseWait {
+                        // the callback has nothing more to send after the half-close of the DUT
+                        b_fin = true;
+                    }
+                    if !b_fin && hold_back(c, payload_sz, nagle, &mut counter_xc) {
+                        // wait until the DUT opens its window or acknowledges our data, the packet is dropped
+                        if c.persist_due() {
+                            c.start_persist(&mut wheel_rto_c, c.sock().unwrap(), &rto_config);
+                        }
+                        return 0;
+                    }
+                    c.set_wnd_blocked(false);
                     /*
                     let pp = c.sent_payload_pkts();
                     if pp < 1 {
@@ -645,7 +1741,7 @@ pub fn setup_generator<FPL>(
                         c.inc_sent_payload_pkts();
                         counter_c[TcpStatistics::SentPayload] += 1;
                         // requeue
-                        ready_connection = Some(c.port());
+                        ready_connection = Some(c.sock().unwrap());
                         group_index = 1;
                     } else if pp == fin_by_client && c.state() < TcpState::CloseWait {
                         generate_fin(&mut pdu, c, &mut hs, &me, &servers, &mut counter_c[TcpStatistics::SentFin]);
@@ -655,25 +1751,37 @@ pub fn setup_generator<FPL>(
                     }
                     */
                     if !b_fin {
+                        c.message_segment_sent(payload_sz);
                         c.inc_sent_payload_pkts();
                         counter_c[TcpStatistics::SentPayload] += 1;
-                        c.seqn_nxt = c.seqn_nxt.wrapping_add(tcp_payload_size(pdu) as u32);
-                        if pdu.data_len() < MIN_FRAME_SIZE {
-                            let n_padding_bytes = MIN_FRAME_SIZE - pdu.data_len();
-                            debug!("padding with {} 0x0 bytes", n_padding_bytes);
-                            pdu.increase_payload_size(n_padding_bytes);
-                        }
+                        let flags = SEG_ACK | if pdu.headers().tcp(2).psh_flag() { SEG_PSH } else { 0 };
+                        c.push_unacked(c.seqn_nxt, flags, &pdu.get_payload(2)[..payload_sz], utils::rdtsc_unsafe());
+                        c.seqn_nxt = c.seqn_nxt.wrapping_add(payload_sz as u32);
+                        mark_ect(pdu, c);
+                        pad_frame(pdu);
                         prepare_checksum_and_ttl(pdu);
-                        // requeue
-                        // ready_connection = Some(c.port());
+                        if c.message_pending() || c.has_delivered() || c.state() == TcpState::CloseWait {
+                            // the segments of a message are sent back to back, reassembled payload of the DUT
+                            // is answered segment by segment,
+                            // in CLOSE-WAIT the callback is invoked until it closes our direction
+                            ready_connection = Some(c.sock().unwrap());
+                        }
                         group_index = 1;
                     } else {
                         generate_fin(pdu, c, &me, &servers);
-                        counter_c[TcpStatistics::SentFin] += 1;
-                        c.set_release_cause(ReleaseCause::ActiveClose);
-                        c.push_state(TcpState::FinWait1);
+                        if c.state() == TcpState::CloseWait {
+                            counter_c[TcpStatistics::SentFinPssv] += 1;
+                            c.push_state(TcpState::LastAck);
+                        } else {
+                            counter_c[TcpStatistics::SentFin] += 1;
+                            c.set_release_cause(ReleaseCause::ActiveClose);
+                            c.push_state(TcpState::FinWait1);
+                        }
+                        let sock = c.sock().unwrap();
+                        c.restart_timeout(&mut wheel_c, timeouts.fin_wait() * system_data.cpu_clock / 1000, sock, (0, 0));
                         group_index = 1;
                     }
+                    c.start_rto(&mut wheel_rto_c, c.sock().unwrap());
                     #[cfg(feature = "profiling")]
                     time_adders[5].add_diff(utils::rdtsc_unsafe() - timestamp_entry);
                 } else {
@@ -685,6 +1793,109 @@ pub fn setup_generator<FPL>(
                 //    cm_c.set_ready_connection(port, &payload_injector_ready_flag);
                 //}
             }
+            // timer triggered segments
+            (PRIVATE_ETYPE_PACKET, 3) => {
+                if let Some(c) = cm_c.get_rtx_connection() {
+                    let server_index = c.server_index();
+                    let sock = c.sock().unwrap();
+                    if retransmit_segment(pdu, c, sock, &me, &servers[server_index], &option_config) {
+                        counter_xc[TcpExtStatistics::SentRetransmission] += 1;
+                        group_index = 1;
+                    } else {
+                        counter_xc[TcpExtStatistics::TailroomExceeded] += 1;
+                    }
+                    if c.take_fast_rtx() {
+                        c.restart_rto(&mut wheel_rto_c, sock, (0, 0));
+                        counter_xc[TcpExtStatistics::FastRetransmission] += 1;
+                    } else {
+                        if c.backoff_rto(&rto_config) == 1 {
+                            c.congestion_timeout(utils::rdtsc_unsafe());
+                        }
+                        c.start_rto(&mut wheel_rto_c, sock);
+                    }
+                } else if let Some(c) = cm_s.get_rtx_connection() {
+                    let sock = c.sock().unwrap();
+                    let peer = server_peer(c.peer_mac, sock);
+                    if retransmit_segment(pdu, c, (me.ip, server_listen_port), &me, &peer, &option_config) {
+                        counter_xs[TcpExtStatistics::SentRetransmission] += 1;
+                        group_index = 1;
+                    } else {
+                        counter_xs[TcpExtStatistics::TailroomExceeded] += 1;
+                    }
+                    c.backoff_rto(&rto_config);
+                    c.start_rto(&mut wheel_rto_s, sock);
+                } else if let Some(c) = cm_c.get_ack_connection() {
+                    let server_index = c.server_index();
+                    let sock = c.sock().unwrap();
+                    let seqn = c.seqn_nxt;
+                    generate_ack(pdu, c, seqn, sock, &me, &servers[server_index]);
+                    counter_xc[TcpExtStatistics::SentPureAck] += 1;
+                    counter_xc[TcpExtStatistics::DelayedAckTimeout] += 1;
+                    group_index = 1;
+                } else if let Some(c) = cm_c.get_persist_connection() {
+                    // the window probe carries no data, the DUT answers with its current window
+                    let server_index = c.server_index();
+                    let sock = c.sock().unwrap();
+                    let seqn = c.seqn_una.wrapping_sub(1);
+                    generate_ack(pdu, c, seqn, sock, &me, &servers[server_index]);
+                    c.inc_persist_backoffs();
+                    c.start_persist(&mut wheel_rto_c, sock, &rto_config);
+                    counter_xc[TcpExtStatistics::SentWindowProbe] += 1;
+                    group_index = 1;
+                } else if let Some(c) = cm_c.get_keepalive_connection() {
+                    let server_index = c.server_index();
+                    let sock = c.sock().unwrap();
+                    let seqn = c.seqn_una.wrapping_sub(1);
+                    generate_ack(pdu, c, seqn, sock, &me, &servers[server_index]);
+                    counter_xc[TcpExtStatistics::SentKeepalive] += 1;
+                    group_index = 1;
+                } else if let Some(c) = cm_s.get_keepalive_connection() {
+                    let peer = server_peer(c.peer_mac, c.sock().unwrap());
+                    let seqn = c.seqn_una.wrapping_sub(1);
+                    generate_ack(pdu, c, seqn, (me.ip, server_listen_port), &me, &peer);
+                    counter_xs[TcpExtStatistics::SentKeepalive] += 1;
+                    group_index = 1;
+                } else if let Some(c) = cm_s.get_delivery_connection() {
+                    // reassembled payload is echoed segment by segment, like in-order payload
+                    let sock = c.sock().unwrap();
+                    let peer = server_peer(c.peer_mac, sock);
+                    let payload = next_delivered(c, &mut counter_s).unwrap();
+                    let b_fin = c.recv_payload_pkts() >= fin_by_server && !c.has_delivered();
+                    if s_echo_delivered(pdu, c, (me.ip, server_listen_port), &me, &peer, &payload, b_fin) {
+                        if b_fin {
+                            counter_s[TcpStatistics::SentFin] += 1;
+                            c.set_release_cause(ReleaseCause::ActiveClose);
+                            c.push_state(TcpState::FinWait1);
+                        }
+                        counter_s[TcpStatistics::SentPayload] += 1;
+                        c.inc_sent_payload_pkts();
+                        c.start_rto(&mut wheel_rto_s, sock);
+                        group_index = 1;
+                    } else {
+                        counter_xs[TcpExtStatistics::TailroomExceeded] += 1;
+                    }
+                    if c.has_delivered() {
+                        cm_s.set_delivery_ready(sock);
+                    }
+                } else if let Some(reset) = cm_c.get_rst() {
+                    let sock = reset.sock;
+                    generate_rst(pdu, &reset, sock, &me, &servers[reset.server_index]);
+                    counter_xc[TcpExtStatistics::SentRstTimeout] += 1;
+                    group_index = 1;
+                } else if let Some(reset) = cm_s.get_rst() {
+                    let peer = server_peer(reset.peer_mac, reset.sock);
+                    generate_rst(pdu, &reset, (me.ip, server_listen_port), &me, &peer);
+                    counter_xs[TcpExtStatistics::SentRstTimeout] += 1;
+                    group_index = 1;
+                } else if let Some(ip) = arp_ready.pop_front() {
+                    generate_arp_request(pdu, ip, &me);
+                    group_index = 1;
+                } else {
+                    if timer_injector_runs() {
+                        timer_injector_stop();
+                    }
+                }
+            }
             (PRIVATE_ETYPE_PACKET, _) => {
                 error!("received unknown dst port from PacketInjector");
             }
@@ -717,15 +1928,19 @@ pub fn setup_generator<FPL>(
                             hold.mean(),
                             hold.max_at().0,
                             hold.max_at().1
-                        )
+                        );
+                        info!("{} client: {}", thread_id, counter_xc);
+                        info!("{} client: ports in TIME_WAIT= {}", thread_id, cm_c.time_wait_connections());
+                        info!("{} client: SYNs skipped per unresolved target= {:?}", thread_id, skipped_syns);
+                        info!("{} server: {}", thread_id, counter_xs);
                     }
                     Ok(MessageTo::FetchCRecords) => {
                         //trace!("{} got FetchCrecords", thread_id);
                         tx_clone
                             .send(MessageFrom::CRecords(
                                 pipeline_id_clone.clone(),
-                                cm_c.fetch_c_records(),
-                                cm_s.fetch_c_records(),
+                                cm_c.fetch_records(),
+                                cm_s.fetch_records(),
                             ))
                             .unwrap();
                     }
@@ -741,8 +1956,29 @@ pub fn setup_generator<FPL>(
                 }
                 // check for timeouts
                 if ticks % wheel_tick_reduction_factor == 0 {
-                    cm_c.release_timeouts(&utils::rdtsc_unsafe(), &mut wheel_c);
-                    cm_s.release_timeouts(&utils::rdtsc_unsafe(), &mut wheel_s);
+                    counter_xc[TcpExtStatistics::KeepaliveTimeout] +=
+                        cm_c.release_timeouts(&utils::rdtsc_unsafe(), &mut wheel_c, &mut wheel_rto_c, &keepalive);
+                    counter_xs[TcpExtStatistics::KeepaliveTimeout] +=
+                        cm_s.release_timeouts(&utils::rdtsc_unsafe(), &mut wheel_s, &mut wheel_rto_s, &keepalive);
+                    counter_xc[TcpExtStatistics::RetransmissionTimeout] +=
+                        cm_c.retransmission_timeouts(&utils::rdtsc_unsafe(), &mut wheel_rto_c, &mut wheel_c, &rto_config);
+                    counter_xs[TcpExtStatistics::RetransmissionTimeout] +=
+                        cm_s.retransmission_timeouts(&utils::rdtsc_unsafe(), &mut wheel_rto_s, &mut wheel_s, &rto_config);
+                    cm_c.delayed_ack_timeouts(&utils::rdtsc_unsafe(), &mut wheel_ack_c);
+                    arp_ready.extend(neighbours.refresh(&mut servers, utils::rdtsc_unsafe()));
+                    let timer_packets = cm_c.rtx_ready_connections()
+                        + cm_s.rtx_ready_connections()
+                        + cm_c.ack_ready_connections()
+                        + cm_c.persist_ready_connections()
+                        + cm_c.keepalive_ready_connections()
+                        + cm_s.keepalive_ready_connections()
+                        + cm_s.delivery_ready_connections()
+                        + cm_c.rst_ready_connections()
+                        + cm_s.rst_ready_connections()
+                        + arp_ready.len();
+                    if timer_packets > 0 && !timer_injector_runs() {
+                        timer_injector_start();
+                    }
                 }
                 #[cfg(feature = "profiling")]
                 {
@@ -776,17 +2012,35 @@ pub fn setup_generator<FPL>(
                 };
 
                 match opt_c {
-                    None => warn!(
-                        "no state for this packet on server port: src_sock= {:?}, tcp = {:?} ",
-                        src_sock,
-                        pdu.headers().tcp(2)
-                    ),
+                    None => {
+                        warn!(
+                            "no state for this packet on server port: src_sock= {:?}, tcp = {:?} ",
+                            src_sock,
+                            pdu.headers().tcp(2)
+                        );
+                        group_index = reject_segment(pdu, rst_via_kni, &mut counter_xs);
+                    }
                     Some(mut c) => {
                         let old_s_state = c.state().clone();
+                        let challenge = if synchronized_state(old_s_state) {
+                            challenge_segment(pdu, c, &mut challenge_ack_limit, &mut counter_xs)
+                        } else {
+                            None
+                        };
+                        if let Some(index) = challenge {
+                            debug!("{} server: challenged segment of {:?}", thread_id, src_sock);
+                            group_index = index;
+                        } else if old_s_state == TcpState::SynReceived && rst_in_window(pdu, c) {
+                            debug!("{} server: connection from DUT ({:?}) reset in SYN-RECEIVED", thread_id, src_sock);
+                            counter_s[TcpStatistics::RecvRst] += 1;
+                            c.push_state(TcpState::Closed);
+                            c.set_release_cause(ReleaseCause::PassiveRst);
+                            b_release_connection_s = true;
+                        }
                         //check seqn
-                        if old_s_state != TcpState::Listen && pdu.headers().tcp(2).seq_num() != c.ackn_nxt {
-                            let diff = pdu.headers().tcp(2).seq_num() as i64 - c.ackn_nxt as i64;
-                            if diff > 0 {
+                        else if old_s_state != TcpState::Listen && pdu.headers().tcp(2).seq_num() != c.ackn_nxt {
+                            let diff = pdu.headers().tcp(2).seq_num().wrapping_sub(c.ackn_nxt) as i32;
+                            if seq_gt(pdu.headers().tcp(2).seq_num(), c.ackn_nxt) {
                                 warn!(
                                     "{} server: unexpected seqn (packet loss?) in state {:?}, seqn differs by {}\ntcp = {}",
                                     thread_id,
@@ -794,6 +2048,17 @@ pub fn setup_generator<FPL>(
                                     diff,
                                     pdu.headers().tcp(2)
                                 );
+                                recv_ecn(pdu, c, &mut counter_xs);
+                                if recv_out_of_order(pdu, c, &mut counter_xs) {
+                                    group_index = 1;
+                                }
+                            } else if pdu.headers().tcp(2).syn_flag() && old_s_state == TcpState::SynReceived {
+                                // our SYN-ACK got lost
+                                debug!("{} server: SYN retransmitted by DUT ({:?})", thread_id, src_sock);
+                                resend_synack(pdu, c, &option_config, &mut counter_xs);
+                                counter_s[TcpStatistics::SentSynAck] += 1;
+                                counter_xs[TcpExtStatistics::SentRetransmission] += 1;
+                                group_index = 1;
                             } else {
                                 debug!(
                                     "{} server: state= {:?}, diff= {}, tcp= {}",
@@ -802,11 +2067,39 @@ pub fn setup_generator<FPL>(
                                     diff,
                                     pdu.headers().tcp(2)
                                 );
+                                if !pdu.headers().tcp(2).rst_flag() {
+                                    // DUT retransmitted a segment, probably our ACK got lost
+                                    send_dup_ack(pdu, c);
+                                    group_index = 1;
+                                }
                             }
                         } else {
+                            if old_s_state != TcpState::Listen {
+                                update_ts_recent(pdu, c);
+                                recv_ecn(pdu, c, &mut counter_xs);
+                            }
+                            if pdu.headers().tcp(2).ack_flag() {
+                                recv_sack(pdu, c, &mut counter_xs);
+                                if c.ack_unacked(pdu.headers().tcp(2).ack_num(), utils::rdtsc_unsafe(), &rto_config) {
+                                    if c.has_unacked() {
+                                        c.restart_rto(&mut wheel_rto_s, src_sock, (0, 0));
+                                    } else {
+                                        c.stop_rto(&mut wheel_rto_s, (0, 0));
+                                    }
+                                }
+                                let tcp = pdu.headers().tcp(2);
+                                let window = c.scaled_window(tcp.window_size());
+                                if c.update_snd_wnd(tcp.seq_num(), tcp.ack_num(), window) {
+                                    counter_xs[TcpExtStatistics::RecvZeroWindow] += 1;
+                                }
+                            }
                             // process payload
                             let payload_sz = tcp_payload_size(pdu);
-                            let b_payload = old_s_state >= TcpState::Established && payload_sz > 0;
+                            // payload may come with the ACK, which completes the handshake
+                            let b_handshake_ack = old_s_state == TcpState::SynReceived
+                                && pdu.headers().tcp(2).ack_flag()
+                                && pdu.headers().tcp(2).ack_num() == c.seqn_nxt;
+                            let b_payload = (old_s_state >= TcpState::Established || b_handshake_ack) && payload_sz > 0;
                             if b_payload {
                                 counter_s[TcpStatistics::RecvPayload] += 1;
                                 c.inc_recv_payload_pkts();
@@ -823,16 +2116,46 @@ pub fn setup_generator<FPL>(
                                     }
                                 }
                                 c.ackn_nxt = pdu.headers().tcp(2).seq_num().wrapping_add(payload_sz as u32);
+                                // the segment may fill a gap in front of queued out-of-order payload,
+                                // which the timer injector echoes after our reply to this segment
+                                b_delivery_s = c.reassemble() > 0;
                             }
 
                             if pdu.headers().tcp(2).syn_flag() {
                                 // check flags
                                 if old_s_state == TcpState::Listen {
                                     // replies with a SYN-ACK to client:
-                                    syn_received(pdu, c);
+                                    c.rcv_wnd = rcv_wnd;
+                                    let b_ecn_setup = pdu.headers().tcp(2).ece_flag() && pdu.headers().tcp(2).cwr_flag();
+                                    c.set_ecn(ecn && b_ecn_setup);
+                                    if c.ecn() {
+                                        counter_xs[TcpExtStatistics::EcnConnection] += 1;
+                                    }
+                                    let syn_data = syn_received(
+                                        pdu,
+                                        c,
+                                        &option_config,
+                                        &isn_generator,
+                                        &cookie_generator,
+                                        &mut counter_xs,
+                                    );
+                                    if syn_data > 0 {
+                                        // the SYN-ACK echoes the data of a TCP Fast Open SYN
+                                        counter_s[TcpStatistics::RecvPayload] += 1;
+                                        c.inc_recv_payload_pkts();
+                                        if detailed_records {
+                                            if let Ok(cdata) = deserialize::<CData>(pdu.get_payload(2)) {
+                                                debug!("{} server: received payload with SYN {:?}", thread_id, cdata);
+                                                c.set_uid(cdata.uuid);
+                                            }
+                                        }
+                                        counter_s[TcpStatistics::SentPayload] += 1;
+                                        c.inc_sent_payload_pkts();
+                                    }
+                                    c.init_rtt(&rto_config);
                                     c.set_server_index(rxq as usize); // we misuse this field for the queue number
                                     c.wheel_slot_and_index = wheel_s.schedule(
-                                        &(timeouts.established.unwrap() * system_data.cpu_clock / 1000),
+                                        &(timeouts.syn_received() * system_data.cpu_clock / 1000),
                                         c.sock().unwrap(),
                                     );
                                     counter_s[TcpStatistics::SentSynAck] += 1;
@@ -854,7 +2177,21 @@ pub fn setup_generator<FPL>(
                                     group_index = 1;
                                 } else {
                                     // DUT wants to close connection
-                                    passive_close(pdu, c, &thread_id, &mut counter_s);
+                                    c.ackn_nxt = ackn_for_fin(pdu);
+                                    if b_payload {
+                                        // half-close of the DUT, we still echo its payload, together with our FIN
+                                        counter_s[TcpStatistics::RecvFin] += 1;
+                                        c.set_release_cause(ReleaseCause::PassiveClose);
+                                        c.push_state(TcpState::CloseWait);
+                                        c.push_state(TcpState::LastAck);
+                                        s_reply_with_payload(pdu, &mut c, true);
+                                        counter_s[TcpStatistics::SentPayload] += 1;
+                                        counter_s[TcpStatistics::SentFinPssv] += 1;
+                                        counter_s[TcpStatistics::SentAck4Fin] += 1;
+                                        c.inc_sent_payload_pkts();
+                                    } else {
+                                        passive_close(pdu, c, &thread_id, &mut counter_s);
+                                    }
                                     group_index = 1;
                                 }
                                 #[cfg(feature = "profiling")]
@@ -872,6 +2209,15 @@ pub fn setup_generator<FPL>(
                                         c.push_state(TcpState::Established);
                                         counter_s[TcpStatistics::RecvSynAck2] += 1;
                                         debug!("{} server: connection from DUT ({:?}) established", thread_id, src_sock);
+                                        let received = c.recv_payload_pkts();
+                                        if !b_payload && received > 0 && received >= fin_by_server {
+                                            // we echoed the data of a TCP Fast Open SYN already, now we close
+                                            counter_s[TcpStatistics::SentFin] += 1;
+                                            c.set_release_cause(ReleaseCause::ActiveClose);
+                                            c.push_state(TcpState::FinWait1);
+                                            s_reply_with_payload(pdu, &mut c, true);
+                                            group_index = 1;
+                                        }
                                         #[cfg(feature = "profiling")]
                                         time_adders[2].add_diff(utils::rdtsc_unsafe() - timestamp_entry);
                                     }
@@ -897,8 +2243,20 @@ pub fn setup_generator<FPL>(
                                 }
                             }
 
-                            if b_payload && old_s_state == TcpState::Established {
-                                let b_fin = c.recv_payload_pkts() >= fin_by_server;
+                            if b_payload
+                                && (c.state() == TcpState::FinWait1 || c.state() == TcpState::FinWait2)
+                                && !pdu.headers().tcp(2).fin_flag()
+                            {
+                                // half-close, we have closed our direction, but still receive payload of the DUT
+                                send_dup_ack(pdu, c);
+                                counter_xs[TcpExtStatistics::SentPureAck] += 1;
+                                group_index = 1;
+                            }
+                            if b_payload
+                                && (old_s_state == TcpState::Established || old_s_state == TcpState::SynReceived)
+                                && c.state() == TcpState::Established
+                            {
+                                let b_fin = c.recv_payload_pkts() >= fin_by_server && !c.has_delivered();
                                 if b_fin {
                                     //trace!("server: reply with payload and FIN");
                                     counter_s[TcpStatistics::SentFin] += 1;
@@ -907,12 +2265,25 @@ pub fn setup_generator<FPL>(
                                 } else {
                                     //trace!("server: reply with payload");
                                 }
-                                // sets also c.ackn_nxt
                                 s_reply_with_payload(pdu, &mut c, b_fin);
                                 counter_s[TcpStatistics::SentPayload] += 1;
                                 c.inc_sent_payload_pkts();
                                 group_index = 1;
                             }
+                            // start the retransmission timer for segments which we have sent in reply
+                            if c.has_unacked() && c.state() != TcpState::Closed {
+                                c.start_rto(&mut wheel_rto_s, src_sock);
+                            }
+                            // the timeout follows the state, while data may flow it is restarted by each segment of the DUT,
+                            // the timeout of SYN-RECEIVED is scheduled with the SYN-ACK
+                            let state = c.state();
+                            if old_s_state != TcpState::Listen
+                                && state != TcpState::Closed
+                                && (state != old_s_state || transfer_state(state))
+                            {
+                                let timeout = timeouts.for_state(state) * system_data.cpu_clock / 1000;
+                                c.restart_timeout(&mut wheel_s, timeout, src_sock, (0, 0));
+                            }
                         }
 
                         #[cfg(feature = "profiling")]
@@ -929,13 +2300,13 @@ pub fn setup_generator<FPL>(
                 if !cm_c.owns_tcp_port(client_port) {
                     error!("flow steering failed {}", pdu.headers().tcp(2));
                 }
-                let c = cm_c.get_mut_by_port(client_port);
+                let c = cm_c.get_mut_by_sock(&dst_sock);
                 #[cfg(feature = "profiling")]
                 time_adders[0].add_diff(utils::rdtsc_unsafe() - timestamp_entry);
                 match c {
                     None => {
                         warn!(
-                            "{} @ {} client: engine has no state for port {} ({}-{}), sending to KNI i/f",
+                            "{} @ {} client: engine has no state for port {} ({}-{}), rejecting segment",
                             thread_id,
                             utils::rdtsc_unsafe().separated_string(),
                             client_port,
@@ -944,21 +2315,50 @@ pub fn setup_generator<FPL>(
                             //Ipv4Addr::from(hs.ip.dst()),
                             // hs.tcp.dst_port(),
                         );
-                        // we send this to KNI which handles out-of-order TCP, e.g. by sending RST
-                        group_index = 2;
+                        group_index = reject_segment(pdu, rst_via_kni, &mut counter_xc);
                     }
                     Some(mut c) => {
                         //debug!("incoming packet for connection {}", c);
                         let old_c_state = c.state().clone();
-
+                        let b_message_pending = c.message_pending();
+                        let challenge = if synchronized_state(old_c_state) && !c.in_time_wait() {
+                            challenge_segment(pdu, c, &mut challenge_ack_limit, &mut counter_xc)
+                        } else {
+                            None
+                        };
+
+                        if c.in_time_wait() {
+                            if pdu.headers().tcp(2).fin_flag() && !pdu.headers().tcp(2).rst_flag() {
+                                // the DUT retransmitted its FIN, our ACK got lost
+                                send_dup_ack(pdu, c);
+                                counter_c[TcpStatistics::SentAck4Fin] += 1;
+                                b_time_wait_c = true;
+                                group_index = 1;
+                            } else {
+                                // RSTs are ignored as well (RFC 1337)
+                                debug!(
+                                    "{} client: port {} in TIME_WAIT, dropping {}",
+                                    thread_id,
+                                    c.port(),
+                                    pdu.headers().tcp(2)
+                                );
+                            }
+                        } else if let Some(index) = challenge {
+                            debug!("{} client: challenged segment on port {}", thread_id, c.port());
+                            group_index = index;
+                        }
                         //check seqn
-                        if old_c_state != TcpState::SynSent && pdu.headers().tcp(2).seq_num() != c.ackn_nxt {
-                            let diff = pdu.headers().tcp(2).seq_num() as i64 - c.ackn_nxt as i64;
-                            if diff > 0 {
+                        else if old_c_state != TcpState::SynSent && pdu.headers().tcp(2).seq_num() != c.ackn_nxt {
+                            let diff = pdu.headers().tcp(2).seq_num().wrapping_sub(c.ackn_nxt) as i32;
+                            if seq_gt(pdu.headers().tcp(2).seq_num(), c.ackn_nxt) {
                                 warn!(
                                     "{} client: unexpected sequence number (packet loss?) in state {:?}, seqn differs by {}\ntcp = { }",
                                     thread_id, old_c_state, diff, pdu.headers().tcp(2)
                                 );
+                                recv_ecn(pdu, c, &mut counter_xc);
+                                if recv_out_of_order(pdu, c, &mut counter_xc) {
+                                    group_index = 1;
+                                }
                             } else {
                                 debug!(
                                     "{} state= {:?}, diff= {}, tcp= {}",
@@ -967,16 +2367,111 @@ pub fn setup_generator<FPL>(
                                     diff,
                                     pdu.headers().tcp(2)
                                 );
+                                if !pdu.headers().tcp(2).rst_flag() {
+                                    // DUT retransmitted a segment, probably our ACK got lost
+                                    send_dup_ack(pdu, c);
+                                    group_index = 1;
+                                }
                             }
                         } else {
+                            if old_c_state != TcpState::SynSent {
+                                update_ts_recent(pdu, c);
+                                recv_ecn(pdu, c, &mut counter_xc);
+                            }
+                            if pdu.headers().tcp(2).ack_flag() {
+                                recv_sack(pdu, c, &mut counter_xc);
+                                let seqn_una = c.seqn_una;
+                                if c.ack_unacked(pdu.headers().tcp(2).ack_num(), utils::rdtsc_unsafe(), &rto_config) {
+                                    if c.has_unacked() {
+                                        c.restart_rto(&mut wheel_rto_c, c.sock().unwrap(), (0, 0));
+                                    } else {
+                                        c.stop_rto(&mut wheel_rto_c, (0, 0));
+                                    }
+                                    if c.congestion_ack(c.seqn_una.wrapping_sub(seqn_una), utils::rdtsc_unsafe()) {
+                                        // partial ACK in fast recovery
+                                        fast_rtx_connection = Some(c.sock().unwrap());
+                                    }
+                                } else if is_dup_ack(pdu, c) && c.congestion_dup_ack(utils::rdtsc_unsafe()) {
+                                    debug!("{} client: fast retransmission on port {}", thread_id, c.port());
+                                    fast_rtx_connection = Some(c.sock().unwrap());
+                                }
+                                if pdu.headers().tcp(2).syn_flag() {
+                                    let peer_options = parse_tcp_options(pdu);
+                                    c.options.negotiate(&option_config, &peer_options);
+                                    let mss = c.options.mss;
+                                    c.set_mss(mss);
+                                    {
+                                        let tcp = pdu.headers().tcp(2);
+                                        if old_c_state == TcpState::SynSent {
+                                            c.irs = tcp.seq_num();
+                                        }
+                                        c.init_snd_wnd(tcp.seq_num(), tcp.ack_num(), tcp.window_size() as u32);
+                                    }
+                                    if c.ecn() && old_c_state == TcpState::SynSent {
+                                        // the DUT agrees to ECN with an ECN-setup SYN-ACK (RFC 3168, 6.1.1)
+                                        let b_ecn = pdu.headers().tcp(2).ece_flag() && !pdu.headers().tcp(2).cwr_flag();
+                                        c.set_ecn(b_ecn);
+                                        if b_ecn {
+                                            counter_xc[TcpExtStatistics::EcnConnection] += 1;
+                                        }
+                                    }
+                                    if option_config.fast_open && old_c_state == TcpState::SynSent {
+                                        match peer_options.fast_open {
+                                            Some(cookie) if !cookie.is_request() => {
+                                                cookie_cache.insert(c.server_index(), cookie)
+                                            }
+                                            _ => (),
+                                        }
+                                        if c.sent_payload_pkts() > 0 && c.has_unacked() {
+                                            // the DUT acknowledged only the SYN, we resend our data immediately,
+                                            // and request a new cookie next time, unless we got one (RFC 7413, 4.2.2)
+                                            counter_xc[TcpExtStatistics::FastOpenDataRejected] += 1;
+                                            if peer_options.fast_open.is_none() {
+                                                cookie_cache.remove(c.server_index());
+                                            }
+                                            c.request_fast_rtx();
+                                            fast_rtx_connection = Some(c.sock().unwrap());
+                                        } else if c.sent_payload_pkts() > 0 {
+                                            counter_xc[TcpExtStatistics::FastOpenDataAcked] += 1;
+                                        }
+                                    }
+                                } else {
+                                    let tcp = pdu.headers().tcp(2);
+                                    let window = c.scaled_window(tcp.window_size());
+                                    if c.update_snd_wnd(tcp.seq_num(), tcp.ack_num(), window) {
+                                        counter_xc[TcpExtStatistics::RecvZeroWindow] += 1;
+                                    }
+                                    c.stop_persist(&mut wheel_rto_c, (0, 0));
+                                }
+                                if c.wnd_blocked()
+                                    && c.usable_window() > 0
+                                    && (!nagle || !c.has_unacked())
+                                    && (old_c_state == TcpState::Established || old_c_state == TcpState::CloseWait)
+                                {
+                                    // the DUT opened its window or, with Nagle's algorithm, acknowledged all our data,
+                                    // let the payload injector send the deferred segment
+                                    c.set_wnd_blocked(false);
+                                    ready_connection = Some(c.sock().unwrap());
+                                }
+                            }
                             //check for payload
                             let payload_sz = tcp_payload_size(pdu);
-                            let b_payload = old_c_state >= TcpState::Established && payload_sz > 0;
+                            // a SYN-ACK may carry the answer to the data in our TCP Fast Open SYN
+                            let b_synack_data = old_c_state == TcpState::SynSent
+                                && pdu.headers().tcp(2).syn_flag()
+                                && pdu.headers().tcp(2).ack_flag();
+                            let b_payload = (old_c_state >= TcpState::Established || b_synack_data) && payload_sz > 0;
                             if b_payload {
                                 counter_c[TcpStatistics::RecvPayload] += 1;
                                 c.inc_recv_payload_pkts();
                                 //trace!("client: got payload, count= {}", c.sent_payload_pkts());
-                                c.ackn_nxt = pdu.headers().tcp(2).seq_num().wrapping_add(payload_sz as u32);
+                                let seqn = pdu.headers().tcp(2).seq_num();
+                                c.ackn_nxt = seqn.wrapping_add(payload_sz as u32 + if b_synack_data { 1 } else { 0 });
+                                // the segment may fill a gap in front of queued out-of-order payload,
+                                // which the payload injector hands to the callback after this segment
+                                if c.reassemble() > 0 {
+                                    ready_connection = Some(c.sock().unwrap());
+                                }
                             }
 
                             if pdu.headers().tcp(2).ack_flag() && pdu.headers().tcp(2).syn_flag() {
@@ -984,15 +2479,24 @@ pub fn setup_generator<FPL>(
                                 counter_c[TcpStatistics::RecvSynAck] += 1;
                                 if old_c_state == TcpState::SynSent {
                                     c.push_state(TcpState::Established);
-                                    ready_connection = Some(c.port());
                                     debug!(
                                         "{} client: connection for port {} to DUT ({:?}) established ",
                                         thread_id,
                                         c.port(),
                                         src_sock
                                     );
-                                    synack_received(pdu, &mut c);
                                     counter_c[TcpStatistics::SentSynAck2] += 1;
+                                    if c.sent_payload_pkts() == 0 || c.message_pending() {
+                                        // unless our first payload went with the SYN already
+                                        ready_connection = Some(c.sock().unwrap());
+                                    }
+                                    if b_payload && c.sent_payload_pkts() > 0 {
+                                        // our reply to the data of the DUT acknowledges the SYN-ACK as well
+                                        group_index =
+                                            c_recv_payload(pdu, c, &mut counter_c, &mut counter_xc, &mut wheel_ack_c);
+                                    } else {
+                                        synack_received(pdu, &mut c);
+                                    }
                                 } else if old_c_state == TcpState::Established {
                                     synack_received(pdu, &mut c);
                                     counter_c[TcpStatistics::SentSynAck2] += 1;
@@ -1012,12 +2516,11 @@ pub fn setup_generator<FPL>(
                                         );
                                     }
                                     if active_close(pdu, c, &mut counter_c, &old_c_state) {
-                                        b_release_connection_c = true;
+                                        b_time_wait_c = true;
                                     }
                                     group_index = 1;
                                 } else {
-                                    passive_close(pdu, c, &thread_id, &mut counter_c);
-                                    group_index = 1;
+                                    group_index = c_recv_fin(pdu, c, &mut counter_c, &mut counter_xc);
                                 }
                                 #[cfg(feature = "profiling")]
                                 time_adders[7].add_diff(utils::rdtsc_unsafe() - timestamp_entry);
@@ -1062,35 +2565,60 @@ pub fn setup_generator<FPL>(
                                             &mut stop_stamp,
                                             &mut hold,
                                         );
-                                        b_release_connection_c = true;
+                                        b_time_wait_c = true;
                                     }
                                     TcpState::Established if b_payload => {
-                                        if !c_recv_payload(pdu, c) {
-                                            counter_c[TcpStatistics::SentPayload] += 1;
-                                        } else {
-                                            counter_c[TcpStatistics::SentFin] += 1;
-                                        }
-                                        group_index = 1;
+                                        group_index =
+                                            c_recv_payload(pdu, c, &mut counter_c, &mut counter_xc, &mut wheel_ack_c);
                                     }
                                     _ => (),
                                 }
                             } else if b_payload && old_c_state == TcpState::Established {
-                                if !c_recv_payload(pdu, c) {
-                                    counter_c[TcpStatistics::SentPayload] += 1;
-                                } else {
-                                    counter_c[TcpStatistics::SentFin] += 1;
-                                }
-                                group_index = 1;
+                                group_index = c_recv_payload(pdu, c, &mut counter_c, &mut counter_xc, &mut wheel_ack_c);
                             } else if !pdu.headers().tcp(2).ack_flag() {
                                 counter_c[TcpStatistics::Unexpected] += 1;
                                 warn!(
-                                    "{} unexpected TCP packet on port {} in client state {:?}, sending to KNI i/f: {}",
+                                    "{} unexpected TCP packet on port {} in client state {:?}, rejecting segment: {}",
                                     thread_id,
                                     pdu.headers().tcp(2).dst_port(),
                                     c.states(),
                                     pdu.headers().tcp(2),
                                 );
-                                group_index = 2;
+                                group_index = reject_segment(pdu, rst_via_kni, &mut counter_xc);
+                            }
+                            if b_payload
+                                && (c.state() == TcpState::FinWait1 || c.state() == TcpState::FinWait2)
+                                && pdu.headers().tcp(2).ack_flag()
+                                && !pdu.headers().tcp(2).fin_flag()
+                            {
+                                // half-close, we have closed our direction, but still receive payload of the DUT
+                                group_index = ack_payload(pdu, c, &mut wheel_ack_c, &mut counter_xc);
+                            }
+                            if !b_message_pending && c.message_pending() && !c.wnd_blocked() {
+                                // we replied with the first segment of a message, the payload injector sends the rest
+                                ready_connection = Some(c.sock().unwrap());
+                            } else if !b_message_pending
+                                && c.state() == TcpState::CloseWait
+                                && old_c_state != TcpState::CloseWait
+                                && !c.wnd_blocked()
+                            {
+                                // after the half-close of the DUT the callback is invoked until it closes our direction
+                                ready_connection = Some(c.sock().unwrap());
+                            }
+                            // start the retransmission timer for segments which we have sent in reply,
+                            // or the persist timer, if our payload is held back by a closed window
+                            if c.has_unacked() && c.state() != TcpState::Closed {
+                                c.start_rto(&mut wheel_rto_c, c.sock().unwrap());
+                            } else if c.persist_due() && c.state() != TcpState::Closed {
+                                c.start_persist(&mut wheel_rto_c, c.sock().unwrap(), &rto_config);
+                            }
+                            // the timeout follows the state, while data may flow it is restarted by each segment of the DUT,
+                            // TIME_WAIT is scheduled below
+                            let state = c.state();
+                            if state != TcpState::Closed && (state != old_c_state || transfer_state(state)) {
+                                let timeout = timeouts.for_state(state) * system_data.cpu_clock / 1000;
+                                let sock = c.sock().unwrap();
+                                c.restart_timeout(&mut wheel_c, timeout, sock, (0, 0));
                             }
                         }
                     }
@@ -1102,19 +2630,34 @@ pub fn setup_generator<FPL>(
         // here we check if we shall release the connection state,
         // need this cumbersome way because of borrow checker for the connection managers
         if b_release_connection_s {
-            cm_s.release(&src_sock, &mut wheel_s);
+            cm_s.release(&src_sock, &mut wheel_s, &mut wheel_rto_s);
             #[cfg(feature = "profiling")]
             time_adders[10].add_diff(utils::rdtscp_unsafe() - timestamp_entry);
+        } else if b_delivery_s {
+            cm_s.set_delivery_ready(src_sock);
+            if !timer_injector_runs() {
+                timer_injector_start();
+            }
+        }
+        if b_time_wait_c {
+            if time_wait > 0 {
+                cm_c.time_wait(dst_sock, &mut wheel_c, &mut wheel_rto_c, time_wait);
+            } else {
+                b_release_connection_c = true;
+            }
         }
         if b_release_connection_c {
-            debug!("releasing client connection on port {}", dst_sock.1);
-            cm_c.release(dst_sock.1, &mut wheel_c);
+            debug!("releasing client connection on socket {:?}", dst_sock);
+            cm_c.release(dst_sock, &mut wheel_c, &mut wheel_rto_c);
             #[cfg(feature = "profiling")]
             time_adders[9].add_diff(utils::rdtscp_unsafe() - timestamp_entry);
         }
-        if let Some(sport) = ready_connection {
-            //trace!("{} connection on port {} is ready", thread_id, sport);
-            cm_c.set_ready_connection(sport, &payload_injector_ready_flag);
+        if let Some(sock) = fast_rtx_connection {
+            cm_c.set_rtx_connection(sock, &timer_injector_ready_flag);
+        }
+        if let Some(sock) = ready_connection {
+            //trace!("{} connection on socket {:?} is ready", thread_id, sock);
+            cm_c.set_ready_connection(sock, &payload_injector_ready_flag);
             #[cfg(feature = "profiling")]
             time_adders[6].add_diff(utils::rdtsc_unsafe() - timestamp_entry);
         }
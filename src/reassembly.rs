@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+
+use retransmission::{seq_lt, seq_leq, seq_gt};
+
+/// maximum number of out-of-order segments, which we keep per connection
+const MAX_REASSEMBLY_SEGMENTS: usize = 32;
+
+/// copies of out-of-order payload of the DUT, ordered by sequence number, when the gap in front of them is filled,
+/// the segments move to the delivered payload, which is handed to the payload logic segment by segment
+#[derive(Debug, Clone)]
+pub struct ReassemblyQueue {
+    segments: VecDeque<(u32, Vec<u8>)>,
+    delivered: VecDeque<Vec<u8>>,
+}
+
+impl ReassemblyQueue {
+    pub fn new() -> ReassemblyQueue {
+        ReassemblyQueue {
+            segments: VecDeque::new(),
+            delivered: VecDeque::new(),
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.delivered.clear();
+    }
+
+    /// true, if no out-of-order segment is queued
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// queues the payload of an out-of-order segment, ackn is the next in-order sequence number, returns false,
+    /// if the payload is outside of the receive window, if it is already queued or if the queue is full
+    pub fn insert(&mut self, seqn: u32, payload: &[u8], ackn: u32, rcv_wnd: u32) -> bool {
+        let end = seqn.wrapping_add(payload.len() as u32);
+        if payload.is_empty() || !seq_gt(seqn, ackn) || seq_gt(end, ackn.wrapping_add(rcv_wnd)) {
+            return false;
+        }
+        if self.segments.len() + self.delivered.len() >= MAX_REASSEMBLY_SEGMENTS {
+            return false;
+        }
+        let mut i = 0;
+        while i < self.segments.len() && seq_lt(self.segments[i].0, seqn) {
+            i += 1;
+        }
+        if i < self.segments.len() && self.segments[i].0 == seqn && self.segments[i].1.len() >= payload.len() {
+            // retransmission of a queued segment
+            return false;
+        }
+        self.segments.insert(i, (seqn, payload.to_vec()));
+        true
+    }
+
+    /// moves the segments which continue the in-order data at ackn to the delivered payload, without the bytes
+    /// which are already in order, returns the new next in-order sequence number and the number of delivered segments
+    pub fn deliver(&mut self, ackn: u32) -> (u32, usize) {
+        let mut ackn = ackn;
+        let mut delivered = 0;
+        while let Some((seqn, mut payload)) = self.segments.pop_front() {
+            if !seq_leq(seqn, ackn) {
+                self.segments.push_front((seqn, payload));
+                break;
+            }
+            let end = seqn.wrapping_add(payload.len() as u32);
+            if seq_gt(end, ackn) {
+                payload.drain(..ackn.wrapping_sub(seqn) as usize);
+                self.delivered.push_back(payload);
+                ackn = end;
+                delivered += 1;
+            }
+        }
+        (ackn, delivered)
+    }
+
+    /// the payload of the next delivered segment
+    #[inline]
+    pub fn pop_delivered(&mut self) -> Option<Vec<u8>> {
+        self.delivered.pop_front()
+    }
+
+    #[inline]
+    pub fn has_delivered(&self) -> bool {
+        !self.delivered.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(len: usize, first: u8) -> Vec<u8> {
+        (0..len).map(|i| first.wrapping_add(i as u8)).collect()
+    }
+
+    #[test]
+    fn insert_checks_window_and_duplicates() {
+        let mut queue = ReassemblyQueue::new();
+        // in-order, empty or beyond the window
+        assert!(!queue.insert(1000, &payload(100, 0), 1000, 5840));
+        assert!(!queue.insert(1100, &[], 1000, 5840));
+        assert!(!queue.insert(6800, &payload(100, 0), 1000, 5840));
+        assert!(queue.is_empty());
+        assert!(queue.insert(1200, &payload(100, 0), 1000, 5840));
+        assert!(!queue.insert(1200, &payload(100, 0), 1000, 5840));
+        assert!(!queue.insert(1200, &payload(50, 0), 1000, 5840));
+        // a retransmission with more payload is queued as well
+        assert!(queue.insert(1200, &payload(200, 0), 1000, 5840));
+    }
+
+    #[test]
+    fn insert_stops_when_full() {
+        let mut queue = ReassemblyQueue::new();
+        for i in 0..MAX_REASSEMBLY_SEGMENTS as u32 {
+            assert!(queue.insert(1100 + i * 200, &payload(100, 0), 1000, 65535));
+        }
+        assert!(!queue.insert(1100 + 64 * 200, &payload(100, 0), 1000, 65535));
+        // delivered payload, which is not yet handed to the payload logic, occupies the queue as well
+        queue.deliver(1100);
+        assert!(!queue.insert(1100 + 64 * 200, &payload(100, 0), 1200, 65535));
+        assert_eq!(queue.pop_delivered(), Some(payload(100, 0)));
+        assert!(queue.insert(1100 + 64 * 200, &payload(100, 0), 1200, 65535));
+    }
+
+    #[test]
+    fn deliver_fills_gaps_in_order() {
+        let mut queue = ReassemblyQueue::new();
+        assert!(queue.insert(1300, &payload(100, 3), 1000, 5840));
+        assert!(queue.insert(1100, &payload(100, 1), 1000, 5840));
+        assert!(queue.insert(1150, &payload(100, 2), 1000, 5840));
+        assert_eq!(queue.deliver(1000), (1000, 0));
+        assert!(!queue.has_delivered());
+        // the overlapping segments are delivered without the bytes in order already, [1250, 1300) is missing
+        assert_eq!(queue.deliver(1100), (1250, 2));
+        assert_eq!(queue.pop_delivered(), Some(payload(100, 1)));
+        assert_eq!(queue.pop_delivered(), Some(payload(100, 2)[50..].to_vec()));
+        assert_eq!(queue.pop_delivered(), None);
+        assert_eq!(queue.deliver(1320), (1400, 1));
+        assert_eq!(queue.pop_delivered(), Some(payload(100, 3)[20..].to_vec()));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn deliver_across_wraparound() {
+        let mut queue = ReassemblyQueue::new();
+        assert!(queue.insert(0xffff_ffc0, &payload(0x80, 0), 0xffff_ff00, 5840));
+        assert!(queue.insert(0x40, &payload(0x40, 0x80), 0xffff_ff00, 5840));
+        assert_eq!(queue.deliver(0xffff_ffc0), (0x80, 2));
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop_delivered(), Some(payload(0x80, 0)));
+        assert_eq!(queue.pop_delivered(), Some(payload(0x40, 0x80)));
+    }
+}
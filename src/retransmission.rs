@@ -0,0 +1,227 @@
+use std::cmp;
+
+/// flags of a segment which we may have to retransmit
+pub const SEG_SYN: u8 = 0x01;
+pub const SEG_FIN: u8 = 0x02;
+pub const SEG_ACK: u8 = 0x04;
+pub const SEG_PSH: u8 = 0x08;
+
+/// sequence number arithmetic modulo 2^32 (RFC 793, section 3.3)
+#[inline]
+pub fn seq_lt(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+#[inline]
+pub fn seq_leq(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) <= 0
+}
+
+#[inline]
+pub fn seq_gt(a: u32, b: u32) -> bool {
+    seq_lt(b, a)
+}
+
+/// a segment which has been sent, but is not yet acknowledged by the peer
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub seqn: u32,
+    pub flags: u8,
+    pub payload: Vec<u8>,
+    /// time stamp of the (last) transmission in cycles
+    pub stamp: u64,
+    /// if retransmitted, the segment is not used for RTT sampling (Karn's algorithm)
+    pub retransmitted: bool,
+    /// the DUT reported the segment in a SACK block
+    pub sacked: bool,
+}
+
+impl Segment {
+    pub fn new(seqn: u32, flags: u8, payload: &[u8], stamp: u64) -> Segment {
+        Segment {
+            seqn,
+            flags,
+            payload: payload.to_vec(),
+            stamp,
+            retransmitted: false,
+            sacked: false,
+        }
+    }
+
+    /// length in sequence number space, SYN and FIN count one each
+    #[inline]
+    pub fn seq_len(&self) -> u32 {
+        self.payload.len() as u32
+            + if self.flags & SEG_SYN != 0 { 1 } else { 0 }
+            + if self.flags & SEG_FIN != 0 { 1 } else { 0 }
+    }
+
+    #[inline]
+    pub fn end_seqn(&self) -> u32 {
+        self.seqn.wrapping_add(self.seq_len())
+    }
+}
+
+/// retransmission parameters, all times in cpu cycles
+#[derive(Debug, Clone, Copy)]
+pub struct RtoConfig {
+    pub initial: u64,
+    pub min: u64,
+    pub max: u64,
+    /// clock granularity G of RFC 6298
+    pub granularity: u64,
+    /// after this number of unsuccessful retransmissions of a segment the connection is timed out
+    pub max_retransmits: u8,
+}
+
+impl RtoConfig {
+    /// times are given in milliseconds
+    pub fn new(cpu_clock: u64, initial: u64, min: u64, max: u64, granularity: u64, max_retransmits: u8) -> RtoConfig {
+        RtoConfig {
+            initial: initial * cpu_clock / 1000,
+            min: min * cpu_clock / 1000,
+            max: max * cpu_clock / 1000,
+            granularity: granularity * cpu_clock / 1000,
+            max_retransmits,
+        }
+    }
+}
+
+/// round trip time estimator and retransmission timeout according to RFC 6298
+#[derive(Debug, Clone, Copy)]
+pub struct RttEstimator {
+    /// smoothed round trip time in cycles, 0 as long as we have no measurement
+    srtt: u64,
+    rttvar: u64,
+    rto: u64,
+    /// number of consecutive back-offs of the timer
+    backoffs: u8,
+}
+
+impl RttEstimator {
+    pub fn new() -> RttEstimator {
+        RttEstimator {
+            srtt: 0,
+            rttvar: 0,
+            rto: 0,
+            backoffs: 0,
+        }
+    }
+
+    #[inline]
+    pub fn initialize(&mut self, config: &RtoConfig) {
+        self.srtt = 0;
+        self.rttvar = 0;
+        self.rto = config.initial;
+        self.backoffs = 0;
+    }
+
+    /// takes a new round trip time sample r (in cycles)
+    pub fn sample(&mut self, r: u64, config: &RtoConfig) {
+        if self.srtt == 0 {
+            self.srtt = cmp::max(r, 1);
+            self.rttvar = r / 2;
+        } else {
+            let delta = if self.srtt > r { self.srtt - r } else { r - self.srtt };
+            self.rttvar = (3 * self.rttvar + delta) / 4;
+            self.srtt = (7 * self.srtt + r) / 8;
+        }
+        let rto = self.srtt + cmp::max(config.granularity, 4 * self.rttvar);
+        self.rto = cmp::min(cmp::max(rto, config.min), config.max);
+    }
+
+    /// doubles the retransmission timeout, returns the number of back-offs
+    #[inline]
+    pub fn backoff(&mut self, config: &RtoConfig) -> u8 {
+        self.rto = cmp::min(self.rto * 2, config.max);
+        self.backoffs += 1;
+        self.backoffs
+    }
+
+    /// new data was acknowledged, we keep the backed-off RTO until the next sample (RFC 6298, 5.7)
+    #[inline]
+    pub fn reset_backoffs(&mut self) {
+        self.backoffs = 0;
+    }
+
+    #[inline]
+    pub fn rto(&self) -> u64 {
+        self.rto
+    }
+
+    #[inline]
+    pub fn srtt(&self) -> u64 {
+        self.srtt
+    }
+
+    #[inline]
+    pub fn backoffs(&self) -> u8 {
+        self.backoffs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // with a cpu clock of 1 MHz one millisecond is 1000 cycles
+    const CPU_CLOCK: u64 = 1_000_000;
+
+    fn config() -> RtoConfig {
+        RtoConfig::new(CPU_CLOCK, 1000, 200, 8000, 1, 5)
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        assert!(seq_lt(1, 2));
+        assert!(!seq_lt(2, 2));
+        assert!(seq_leq(2, 2));
+        assert!(seq_lt(0xffff_fff0, 0x10));
+        assert!(seq_gt(0x10, 0xffff_fff0));
+        assert!(seq_leq(0xffff_ffff, 0));
+        assert!(!seq_gt(0xffff_ffff, 0));
+        // more than 2^31 ahead is behind
+        assert!(seq_lt(0x8000_0001, 0));
+    }
+
+    #[test]
+    fn rto_follows_samples() {
+        let config = config();
+        let mut rtt = RttEstimator::new();
+        rtt.initialize(&config);
+        assert_eq!(rtt.rto(), 1_000_000);
+        // first sample: srtt = r, rttvar = r/2, rto = r + 4*rttvar
+        rtt.sample(100_000, &config);
+        assert_eq!(rtt.srtt(), 100_000);
+        assert_eq!(rtt.rto(), 300_000);
+        // a constant rtt lets rttvar decay
+        rtt.sample(100_000, &config);
+        assert_eq!(rtt.srtt(), 100_000);
+        assert_eq!(rtt.rto(), 250_000);
+        // the rto is bounded by the minimum
+        for _ in 0..100 {
+            rtt.sample(10_000, &config);
+        }
+        assert_eq!(rtt.rto(), 200_000);
+    }
+
+    #[test]
+    fn backoff_doubles_rto_up_to_max() {
+        let config = config();
+        let mut rtt = RttEstimator::new();
+        rtt.initialize(&config);
+        assert_eq!(rtt.backoff(&config), 1);
+        assert_eq!(rtt.rto(), 2_000_000);
+        assert_eq!(rtt.backoff(&config), 2);
+        assert_eq!(rtt.backoff(&config), 3);
+        assert_eq!(rtt.rto(), 8_000_000);
+        assert_eq!(rtt.backoff(&config), 4);
+        assert_eq!(rtt.rto(), 8_000_000);
+        // the backed-off rto is kept until the next sample
+        rtt.reset_backoffs();
+        assert_eq!(rtt.backoffs(), 0);
+        assert_eq!(rtt.rto(), 8_000_000);
+        rtt.sample(100_000, &config);
+        assert_eq!(rtt.rto(), 300_000);
+    }
+}
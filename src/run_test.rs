@@ -28,6 +28,7 @@ use netfcts::RunTime;
 use netfcts::comm::PipelineId;
 use netfcts::conrecord::HasTcpState;
 use netfcts::system::get_mac_from_ifname;
+use eui48::MacAddress;
 use netfcts::io::print_tcp_counters;
 #[cfg(feature = "profiling")]
 use netfcts::io::print_rx_tx_counters;
@@ -39,7 +40,7 @@ use {CData, L234Data, Connection, Configuration};
 use {MessageFrom, MessageTo};
 use ReleaseCause;
 use {TcpState, TcpStatistics};
-use netfcts::recstore::TEngineStore;
+use EngineRecords;
 
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -53,7 +54,7 @@ pub fn run_test(test_type: TestType) {
     // cannot directly read toml file from command line, as cargo test owns it. Thus we take a detour and read it from a file.
     const INDIRECTION_FILE: &str = "./tests/toml_file.txt";
 
-    let mut run_time: RunTime<Configuration, TEngineStore> = match RunTime::init_indirectly(INDIRECTION_FILE) {
+    let mut run_time: RunTime<Configuration, EngineRecords> = match RunTime::init_indirectly(INDIRECTION_FILE) {
         Ok(run_time) => run_time,
         Err(err) => panic!("failed to initialize RunTime {}", err),
     };
@@ -62,6 +63,14 @@ pub fn run_test(test_type: TestType) {
     run_time.setup_flowdirector().expect("failed to setup flowdirector");
 
     let run_configuration = run_time.run_configuration.clone();
+    for port in run_time.context().expect("no context").ports.values().filter(|p| p.is_physical()) {
+        if let Some(flow_steering) = port.flow_steering_mode() {
+            if let Err(e) = run_configuration.engine_configuration.engine.check_client_pool(flow_steering) {
+                error!("{} in configuration file {}", e, run_time.toml_filename());
+                process::exit(1);
+            }
+        }
+    }
     let configuration = &run_configuration.engine_configuration;
 
     if configuration.test_size.is_none() {
@@ -91,7 +100,8 @@ pub fn run_test(test_type: TestType) {
         .map(|(i, srv_cfg)| L234Data {
             mac: srv_cfg
                 .mac
-                .unwrap_or_else(|| get_mac_from_ifname(srv_cfg.linux_if.as_ref().unwrap()).unwrap()),
+                .or_else(|| srv_cfg.linux_if.as_ref().map(|linux_if| get_mac_from_ifname(linux_if).unwrap()))
+                .unwrap_or(MacAddress::nil()), // resolved with ARP by the pipelines
             ip: u32::from(srv_cfg.ip),
             port: srv_cfg.port,
             server_id: srv_cfg.id.clone(),
@@ -362,11 +372,15 @@ pub fn run_test(test_type: TestType) {
                 match c_records {
                     Some(c_records) if c_records.len() > 0 => {
                         let mut completed_count = 0;
-                        let mut min = c_records.iter().last().unwrap();
+                        let mut min = c_records.con_records.iter().last().unwrap();
                         let mut max = min;
-                        c_records.iter().enumerate().for_each(|(i, c)| {
+                        c_records.iter().enumerate().for_each(|(i, (c, x))| {
                             let line = format!("{:6}: {}\n", i, c);
                             f.write_all(line.as_bytes()).expect("cannot write c_records");
+                            if let Some(x) = x {
+                                f.write_all(format!("        {}\n", x).as_bytes())
+                                    .expect("cannot write c_records");
+                            }
                             if c.states().last().unwrap() == &TcpState::Closed {
                                 completed_count += 1
                             }
@@ -404,9 +418,13 @@ pub fn run_test(test_type: TestType) {
                 info!("Pipeline {}:", p);
                 f.write_all(format!("Pipeline {}:\n", p).as_bytes())
                     .expect("cannot write c_records");
-                c_records.as_ref().unwrap().iter().enumerate().for_each(|(i, c)| {
+                c_records.as_ref().unwrap().iter().enumerate().for_each(|(i, (c, x))| {
                     let line = format!("{:6}: {}\n", i, c);
                     f.write_all(line.as_bytes()).expect("cannot write c_records");
+                    if let Some(x) = x {
+                        f.write_all(format!("        {}\n", x).as_bytes())
+                            .expect("cannot write c_records");
+                    }
                     if (c.release_cause() == ReleaseCause::PassiveClose || c.release_cause() == ReleaseCause::ActiveClose)
                         && c.states().last().unwrap() == &TcpState::Closed
                     {
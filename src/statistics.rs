@@ -0,0 +1,130 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+/// counters which complement the TcpStatistics of netfcts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpExtStatistics {
+    SentRetransmission = 0,
+    RetransmissionTimeout = 1,
+    /// a payload segment was held back, because the send window or the congestion window was exhausted
+    SendWindowFull = 2,
+    /// retransmissions triggered by duplicate or partial ACKs
+    FastRetransmission = 3,
+    /// duplicate ACKs with SACK blocks for out-of-order data of the DUT
+    SentSack = 4,
+    /// ACKs of the DUT with SACK blocks
+    RecvSack = 5,
+    /// RSTs for timed out connections
+    SentRstTimeout = 6,
+    /// out-of-order payload segments of the DUT, which were queued for reassembly
+    RecvOutOfOrder = 7,
+    /// RSTs for segments, which do not belong to a connection
+    SentRstNoConnection = 8,
+    /// segments, which do not belong to a connection, forwarded to the KNI i/f
+    ForwardedToKni = 9,
+    /// challenge ACKs for in-window RSTs, whose sequence number is not the expected one (RFC 5961)
+    SentChallengeAckRst = 10,
+    /// challenge ACKs for SYNs in a synchronized state (RFC 5961)
+    SentChallengeAckSyn = 11,
+    /// RSTs outside of the receive window, which were silently dropped (RFC 5961)
+    DroppedRstOutOfWindow = 12,
+    /// ACKs without data for payload of the DUT, sent immediately or when the delayed ACK timer expired
+    SentPureAck = 13,
+    /// pure ACKs sent by the delayed ACK timer
+    DelayedAckTimeout = 14,
+    /// a payload segment smaller than the MSS was held back by Nagle's algorithm
+    NagleDelayed = 15,
+    /// SYNs with a TCP Fast Open cookie request, sent by the client or received by the server
+    FastOpenCookieRequest = 16,
+    /// SYNs with data and a Fast Open cookie, sent by the client or received by the server
+    FastOpenSynData = 17,
+    /// data in a SYN, which was acknowledged by the SYN-ACK
+    FastOpenDataAcked = 18,
+    /// data in a SYN, which was not acknowledged by the SYN-ACK, e.g. because of an invalid cookie
+    FastOpenDataRejected = 19,
+    /// connections, which negotiated ECN (RFC 3168)
+    EcnConnection = 20,
+    /// segments of the DUT with a CE mark
+    RecvCe = 21,
+    /// reductions of the congestion window upon ECE of the DUT
+    EcnWindowReduction = 22,
+    /// keepalive probes for idle connections
+    SentKeepalive = 23,
+    /// connections, which were timed out, because the DUT did not answer our keepalive probes
+    KeepaliveTimeout = 24,
+    /// ACKs of the DUT, which closed the send window
+    RecvZeroWindow = 25,
+    /// window probes, sent by the persist timer while the send window is closed
+    SentWindowProbe = 26,
+    /// payload segments, which were not sent, because the mbuf has not enough tail room
+    TailroomExceeded = 27,
+    /// challenge ACKs, which were suppressed by the rate limit (RFC 5961, 7)
+    ChallengeAckLimited = 28,
+    Count = 29,
+}
+
+const ALL_EXT_STATISTICS: [TcpExtStatistics; TcpExtStatistics::Count as usize] = [
+    TcpExtStatistics::SentRetransmission,
+    TcpExtStatistics::RetransmissionTimeout,
+    TcpExtStatistics::SendWindowFull,
+    TcpExtStatistics::FastRetransmission,
+    TcpExtStatistics::SentSack,
+    TcpExtStatistics::RecvSack,
+    TcpExtStatistics::SentRstTimeout,
+    TcpExtStatistics::RecvOutOfOrder,
+    TcpExtStatistics::SentRstNoConnection,
+    TcpExtStatistics::ForwardedToKni,
+    TcpExtStatistics::SentChallengeAckRst,
+    TcpExtStatistics::SentChallengeAckSyn,
+    TcpExtStatistics::DroppedRstOutOfWindow,
+    TcpExtStatistics::SentPureAck,
+    TcpExtStatistics::DelayedAckTimeout,
+    TcpExtStatistics::NagleDelayed,
+    TcpExtStatistics::FastOpenCookieRequest,
+    TcpExtStatistics::FastOpenSynData,
+    TcpExtStatistics::FastOpenDataAcked,
+    TcpExtStatistics::FastOpenDataRejected,
+    TcpExtStatistics::EcnConnection,
+    TcpExtStatistics::RecvCe,
+    TcpExtStatistics::EcnWindowReduction,
+    TcpExtStatistics::SentKeepalive,
+    TcpExtStatistics::KeepaliveTimeout,
+    TcpExtStatistics::RecvZeroWindow,
+    TcpExtStatistics::SentWindowProbe,
+    TcpExtStatistics::TailroomExceeded,
+    TcpExtStatistics::ChallengeAckLimited,
+];
+
+#[derive(Debug, Clone)]
+pub struct TcpExtCounter([usize; TcpExtStatistics::Count as usize]);
+
+impl TcpExtCounter {
+    pub fn new() -> TcpExtCounter {
+        TcpExtCounter([0; TcpExtStatistics::Count as usize])
+    }
+}
+
+impl Index<TcpExtStatistics> for TcpExtCounter {
+    type Output = usize;
+
+    #[inline]
+    fn index(&self, stat: TcpExtStatistics) -> &usize {
+        &self.0[stat as usize]
+    }
+}
+
+impl IndexMut<TcpExtStatistics> for TcpExtCounter {
+    #[inline]
+    fn index_mut(&mut self, stat: TcpExtStatistics) -> &mut usize {
+        &mut self.0[stat as usize]
+    }
+}
+
+impl fmt::Display for TcpExtCounter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for stat in ALL_EXT_STATISTICS.iter() {
+            write!(f, "{:?}= {}, ", stat, self[*stat])?;
+        }
+        Ok(())
+    }
+}
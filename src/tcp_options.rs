@@ -0,0 +1,628 @@
+use std::cmp;
+
+use e2d2::interface::Pdu;
+use e2d2::utils;
+
+use retransmission::{seq_lt, seq_leq, seq_gt};
+
+const TCPOPT_EOL: u8 = 0;
+const TCPOPT_NOP: u8 = 1;
+const TCPOPT_MSS: u8 = 2;
+const TCPOPT_WSCALE: u8 = 3;
+const TCPOPT_SACK_PERMITTED: u8 = 4;
+const TCPOPT_SACK: u8 = 5;
+const TCPOPT_TIMESTAMP: u8 = 8;
+const TCPOPT_FASTOPEN: u8 = 34;
+
+/// size of the TCP header without options
+const TCP_HEADER_LEN: usize = 20;
+const MAX_OPTIONS_LEN: usize = 40;
+/// maximum shift count of RFC 7323, 2.3
+const MAX_WSCALE: u8 = 14;
+/// the timestamp clock ticks every 2^22 cycles, i.e. every few milliseconds (RFC 7323, 5.4)
+const TS_CLOCK_SHIFT: u32 = 22;
+
+/// maximum number of SACK blocks, which fit into the option space (RFC 2018, 3)
+pub const MAX_SACK_BLOCKS: usize = 4;
+
+/// cookie lengths of RFC 7413, 4.1.1
+const MIN_COOKIE_LEN: usize = 4;
+const MAX_COOKIE_LEN: usize = 16;
+
+/// MSS of the DUT, if it does not send the MSS option (RFC 1122, 4.2.2.6)
+pub const DEFAULT_PEER_MSS: u16 = 536;
+
+/// the options we offer in our SYN or SYN-ACK
+#[derive(Debug, Clone, Copy)]
+pub struct TcpOptionConfig {
+    pub mss: u16,
+    /// our window scale shift count, None if we do not use window scaling
+    pub wscale: Option<u8>,
+    pub sack_permitted: bool,
+    pub timestamps: bool,
+    /// TCP Fast Open (RFC 7413)
+    pub fast_open: bool,
+}
+
+impl TcpOptionConfig {
+    /// derives the shift count from the receive window which we want to advertise
+    pub fn new(
+        mss: u16,
+        rcv_wnd: u32,
+        window_scaling: bool,
+        sack_permitted: bool,
+        timestamps: bool,
+        fast_open: bool,
+    ) -> TcpOptionConfig {
+        let wscale = if window_scaling {
+            let mut shift = 0u8;
+            while rcv_wnd >> shift > 0xFFFF && shift < MAX_WSCALE {
+                shift += 1;
+            }
+            Some(shift)
+        } else {
+            None
+        };
+        TcpOptionConfig {
+            mss,
+            wscale,
+            sack_permitted,
+            timestamps,
+            fast_open,
+        }
+    }
+}
+
+/// a TCP Fast Open cookie (RFC 7413, 4.1.1), an empty cookie is a cookie request
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FastOpenCookie {
+    bytes: [u8; MAX_COOKIE_LEN],
+    len: usize,
+}
+
+impl FastOpenCookie {
+    /// surplus bytes are ignored
+    pub fn new(cookie: &[u8]) -> FastOpenCookie {
+        let len = cmp::min(cookie.len(), MAX_COOKIE_LEN);
+        let mut bytes = [0u8; MAX_COOKIE_LEN];
+        bytes[..len].copy_from_slice(&cookie[..len]);
+        FastOpenCookie { bytes, len }
+    }
+
+    /// zero for a request, otherwise an even number of bytes within the limits
+    #[inline]
+    fn is_valid_len(len: usize) -> bool {
+        len == 0 || len % 2 == 0 && len >= MIN_COOKIE_LEN && len <= MAX_COOKIE_LEN
+    }
+
+    #[inline]
+    pub fn is_request(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// blocks of contiguous sequence numbers, given as left edge and right edge (RFC 2018, 3),
+/// the first block is the most recent one
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SackBlocks {
+    blocks: [(u32, u32); MAX_SACK_BLOCKS],
+    len: usize,
+}
+
+impl SackBlocks {
+    pub fn new() -> SackBlocks {
+        SackBlocks::default()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[(u32, u32)] {
+        &self.blocks[..self.len]
+    }
+
+    /// appends a block as found in a SACK option, surplus blocks are ignored
+    fn push(&mut self, left: u32, right: u32) {
+        if self.len < MAX_SACK_BLOCKS {
+            self.blocks[self.len] = (left, right);
+            self.len += 1;
+        }
+    }
+
+    /// adds the block of a newly received segment in front, blocks which overlap or adjoin it are merged into it,
+    /// if there is no more room, the oldest block is dropped
+    pub fn insert(&mut self, left: u32, right: u32) {
+        let (mut left, mut right) = (left, right);
+        let mut kept = [(0u32, 0u32); MAX_SACK_BLOCKS];
+        let mut n_kept = 0;
+        for &(l, r) in self.as_slice() {
+            if seq_leq(l, right) && seq_leq(left, r) {
+                if seq_lt(l, left) {
+                    left = l;
+                }
+                if seq_gt(r, right) {
+                    right = r;
+                }
+            } else {
+                kept[n_kept] = (l, r);
+                n_kept += 1;
+            }
+        }
+        self.blocks[0] = (left, right);
+        let n_kept = cmp::min(n_kept, MAX_SACK_BLOCKS - 1);
+        self.blocks[1..n_kept + 1].copy_from_slice(&kept[..n_kept]);
+        self.len = n_kept + 1;
+    }
+
+    /// removes the blocks which are covered by the cumulative acknowledgement ackn
+    pub fn remove_acked(&mut self, ackn: u32) {
+        let mut i = 0;
+        while i < self.len {
+            if seq_leq(self.blocks[i].1, ackn) {
+                self.blocks.copy_within(i + 1..self.len, i);
+                self.len -= 1;
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// true, if a block contains the sequence number range [left, right)
+    pub fn covers(&self, left: u32, right: u32) -> bool {
+        self.as_slice()
+            .iter()
+            .any(|&(l, r)| seq_leq(l, left) && seq_leq(right, r))
+    }
+}
+
+/// options as found in a received segment
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpOptions {
+    pub mss: Option<u16>,
+    pub wscale: Option<u8>,
+    pub sack_permitted: bool,
+    /// TSval and TSecr
+    pub timestamp: Option<(u32, u32)>,
+    pub sack: SackBlocks,
+    pub fast_open: Option<FastOpenCookie>,
+}
+
+impl TcpOptions {
+    pub fn parse(options: &[u8]) -> TcpOptions {
+        let mut result = TcpOptions::default();
+        let mut i = 0;
+        while i < options.len() {
+            match options[i] {
+                TCPOPT_EOL => break,
+                TCPOPT_NOP => {
+                    i += 1;
+                    continue;
+                }
+                _ => (),
+            }
+            if i + 1 >= options.len() {
+                break;
+            }
+            let len = options[i + 1] as usize;
+            if len < 2 || i + len > options.len() {
+                debug!("malformed tcp option {} with length {}", options[i], len);
+                break;
+            }
+            let value = &options[i + 2..i + len];
+            match (options[i], len) {
+                (TCPOPT_MSS, 4) => result.mss = Some(be_u16(value)),
+                (TCPOPT_WSCALE, 3) => result.wscale = Some(cmp::min(value[0], MAX_WSCALE)),
+                (TCPOPT_SACK_PERMITTED, 2) => result.sack_permitted = true,
+                (TCPOPT_TIMESTAMP, 10) => result.timestamp = Some((be_u32(&value[0..4]), be_u32(&value[4..8]))),
+                (TCPOPT_SACK, _) if len % 8 == 2 => {
+                    for block in value.chunks(8) {
+                        result.sack.push(be_u32(&block[0..4]), be_u32(&block[4..8]));
+                    }
+                }
+                (TCPOPT_FASTOPEN, _) if FastOpenCookie::is_valid_len(value.len()) => {
+                    result.fast_open = Some(FastOpenCookie::new(value))
+                }
+                _ => (),
+            }
+            i += len;
+        }
+        result
+    }
+}
+
+/// the options which are in effect for a connection after the handshake
+#[derive(Debug, Clone, Copy)]
+pub struct NegotiatedOptions {
+    /// MSS announced by the DUT
+    pub mss: u16,
+    /// window scaling is used in both directions
+    pub wscale_ok: bool,
+    /// shift count of the DUT, applied to the windows we receive
+    pub snd_wscale: u8,
+    /// our shift count, applied to the windows we advertise
+    pub rcv_wscale: u8,
+    pub sack_permitted: bool,
+    pub timestamps: bool,
+    /// the TSval to echo in our next segment
+    pub ts_recent: u32,
+}
+
+impl NegotiatedOptions {
+    pub fn new() -> NegotiatedOptions {
+        NegotiatedOptions {
+            mss: DEFAULT_PEER_MSS,
+            wscale_ok: false,
+            snd_wscale: 0,
+            rcv_wscale: 0,
+            sack_permitted: false,
+            timestamps: false,
+            ts_recent: 0,
+        }
+    }
+
+    /// combines our configuration with the options in the SYN or SYN-ACK of the DUT
+    pub fn negotiate(&mut self, config: &TcpOptionConfig, peer: &TcpOptions) {
+        self.mss = peer.mss.unwrap_or(DEFAULT_PEER_MSS);
+        match (config.wscale, peer.wscale) {
+            (Some(ours), Some(theirs)) => {
+                self.wscale_ok = true;
+                self.rcv_wscale = ours;
+                self.snd_wscale = theirs;
+            }
+            _ => {
+                self.wscale_ok = false;
+                self.rcv_wscale = 0;
+                self.snd_wscale = 0;
+            }
+        }
+        self.sack_permitted = config.sack_permitted && peer.sack_permitted;
+        match peer.timestamp {
+            Some((tsval, _)) if config.timestamps => {
+                self.timestamps = true;
+                self.ts_recent = tsval;
+            }
+            _ => self.timestamps = false,
+        }
+    }
+}
+
+/// a buffer for the options of an outgoing segment
+pub struct OptionBuf {
+    buf: [u8; MAX_OPTIONS_LEN],
+    len: usize,
+}
+
+impl OptionBuf {
+    pub fn new() -> OptionBuf {
+        OptionBuf {
+            buf: [0u8; MAX_OPTIONS_LEN],
+            len: 0,
+        }
+    }
+
+    /// the options of our SYN, if negotiated is None, or of our SYN-ACK
+    pub fn syn_options(config: &TcpOptionConfig, negotiated: Option<&NegotiatedOptions>) -> OptionBuf {
+        let mut options = OptionBuf::new();
+        options.push_mss(config.mss);
+        match negotiated {
+            None => {
+                if let Some(wscale) = config.wscale {
+                    options.push_wscale(wscale);
+                }
+                if config.sack_permitted {
+                    options.push_sack_permitted();
+                }
+                if config.timestamps {
+                    options.push_timestamp(0);
+                }
+            }
+            Some(negotiated) => {
+                if negotiated.wscale_ok {
+                    options.push_wscale(negotiated.rcv_wscale);
+                }
+                if negotiated.sack_permitted {
+                    options.push_sack_permitted();
+                }
+                if negotiated.timestamps {
+                    options.push_timestamp(negotiated.ts_recent);
+                }
+            }
+        }
+        options
+    }
+
+    /// the options of a segment after the handshake, sack_blocks are reported if SACK is permitted
+    pub fn options(negotiated: &NegotiatedOptions, sack_blocks: &SackBlocks) -> OptionBuf {
+        let mut options = OptionBuf::new();
+        if negotiated.timestamps {
+            options.push_timestamp(negotiated.ts_recent);
+        }
+        if negotiated.sack_permitted && !sack_blocks.is_empty() {
+            options.push_sack(sack_blocks);
+        }
+        options
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    pub fn push_mss(&mut self, mss: u16) {
+        let mss = mss.to_be_bytes();
+        self.push(&[TCPOPT_MSS, 4, mss[0], mss[1]]);
+    }
+
+    pub fn push_wscale(&mut self, shift: u8) {
+        self.push(&[TCPOPT_NOP, TCPOPT_WSCALE, 3, shift]);
+    }
+
+    pub fn push_sack_permitted(&mut self) {
+        self.push(&[TCPOPT_NOP, TCPOPT_NOP, TCPOPT_SACK_PERMITTED, 2]);
+    }
+
+    pub fn push_timestamp(&mut self, ts_ecr: u32) {
+        self.push(&[TCPOPT_NOP, TCPOPT_NOP, TCPOPT_TIMESTAMP, 10]);
+        self.push(&ts_value().to_be_bytes());
+        self.push(&ts_ecr.to_be_bytes());
+    }
+
+    /// pushes as many blocks as fit into the remaining option space
+    pub fn push_sack(&mut self, sack_blocks: &SackBlocks) {
+        let n = cmp::min(sack_blocks.len, MAX_OPTIONS_LEN.saturating_sub(self.len + 4) / 8);
+        if n == 0 {
+            return;
+        }
+        self.push(&[TCPOPT_NOP, TCPOPT_NOP, TCPOPT_SACK, 2 + 8 * n as u8]);
+        for &(left, right) in &sack_blocks.as_slice()[..n] {
+            self.push(&left.to_be_bytes());
+            self.push(&right.to_be_bytes());
+        }
+    }
+
+    /// pushes a Fast Open cookie or, if it is empty, a cookie request,
+    /// returns false if it does not fit into the remaining option space
+    pub fn push_fast_open(&mut self, cookie: &FastOpenCookie) -> bool {
+        let len = 2 + cookie.len;
+        let padding = (4 - len % 4) % 4;
+        if self.len + padding + len > MAX_OPTIONS_LEN {
+            return false;
+        }
+        for _ in 0..padding {
+            self.push(&[TCPOPT_NOP]);
+        }
+        self.push(&[TCPOPT_FASTOPEN, len as u8]);
+        self.push(cookie.as_slice());
+        true
+    }
+
+    /// all options are pushed with leading NOPs, so that the length is always a multiple of 4
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// our timestamp clock
+#[inline]
+pub fn ts_value() -> u32 {
+    (utils::rdtsc_unsafe() >> TS_CLOCK_SHIFT) as u32
+}
+
+#[inline]
+fn be_u16(b: &[u8]) -> u16 {
+    (b[0] as u16) << 8 | b[1] as u16
+}
+
+#[inline]
+fn be_u32(b: &[u8]) -> u32 {
+    (b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32
+}
+
+/// the length of the TCP header in p, a data offset below the minimum is taken as a header without options
+#[inline]
+fn tcp_header_len(p: &Pdu) -> usize {
+    cmp::max(p.headers().tcp(2).data_offset() as usize * 4, TCP_HEADER_LEN)
+}
+
+/// the raw options of the TCP header in p, empty if the header is truncated
+#[inline]
+pub fn tcp_options(p: &Pdu) -> &[u8] {
+    let header_len = tcp_header_len(p);
+    let segment = p.get_payload(1);
+    if header_len > segment.len() {
+        return &[];
+    }
+    &segment[TCP_HEADER_LEN..header_len]
+}
+
+#[inline]
+pub fn parse_tcp_options(p: &Pdu) -> TcpOptions {
+    TcpOptions::parse(tcp_options(p))
+}
+
+/// replaces the options of the TCP header in p, any payload is moved accordingly,
+/// the payload is limited to the bytes which are present, if the length fields are inconsistent
+pub fn set_tcp_options(p: &mut Pdu, options: &[u8]) {
+    let old_header_len = tcp_header_len(p);
+    let new_header_len = TCP_HEADER_LEN + options.len();
+    let ip_header_len = p.headers().ip(1).ihl() as usize * 4;
+    let ip_len = p.headers().ip(1).length() as usize;
+    let available = p.get_payload(1).len();
+    let payload_len = cmp::min(
+        ip_len.saturating_sub(ip_header_len + old_header_len),
+        available.saturating_sub(old_header_len),
+    );
+    if p.headers().tcp(2).data_offset() as usize * 4 == new_header_len && options.len() == 0 {
+        return;
+    }
+    let new_segment_len = new_header_len + payload_len;
+    if available < new_segment_len {
+        p.add_to_payload_tail(new_segment_len - available)
+            .expect("insufficient tail room for tcp options");
+    }
+    {
+        let segment = p.get_payload_mut(1);
+        if payload_len > 0 {
+            segment.copy_within(old_header_len..old_header_len + payload_len, new_header_len);
+        }
+        segment[TCP_HEADER_LEN..new_header_len].copy_from_slice(options);
+    }
+    p.headers_mut().tcp_mut(2).set_data_offset((new_header_len / 4) as u8);
+    p.headers_mut()
+        .ip_mut(1)
+        .set_length((ip_header_len + new_segment_len) as u16);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_options_of_a_syn() {
+        let options = [
+            2, 4, 0x05, 0xb4, // MSS 1460
+            4, 2, // SACK permitted
+            8, 10, 0, 0, 0, 1, 0, 0, 0, 2, // TSval 1, TSecr 2
+            1, // NOP
+            3, 3, 15, // window scale above the maximum
+            0, 0xff, // EOL ends the parsing
+        ];
+        let parsed = TcpOptions::parse(&options);
+        assert_eq!(parsed.mss, Some(1460));
+        assert!(parsed.sack_permitted);
+        assert_eq!(parsed.timestamp, Some((1, 2)));
+        assert_eq!(parsed.wscale, Some(MAX_WSCALE));
+        assert!(parsed.sack.is_empty());
+        assert_eq!(parsed.fast_open, None);
+    }
+
+    #[test]
+    fn parse_stops_at_malformed_options() {
+        // the length of the MSS exceeds the options
+        let parsed = TcpOptions::parse(&[4, 2, 2, 8, 0x05, 0xb4]);
+        assert!(parsed.sack_permitted);
+        assert_eq!(parsed.mss, None);
+        // a length below 2 would loop forever
+        let parsed = TcpOptions::parse(&[3, 0, 2, 4, 0x05, 0xb4]);
+        assert_eq!(parsed.mss, None);
+        // options with an unexpected length are skipped
+        let parsed = TcpOptions::parse(&[2, 3, 0x05, 2, 4, 0x02, 0x18]);
+        assert_eq!(parsed.mss, Some(536));
+        let parsed = TcpOptions::parse(&[1, 1, 5, 2 + 8, 0, 0, 0, 1]);
+        assert!(parsed.sack.is_empty());
+    }
+
+    #[test]
+    fn emitted_options_parse_again() {
+        let config = TcpOptionConfig::new(1460, 262144, true, true, true, false);
+        assert_eq!(config.wscale, Some(3));
+        let options = OptionBuf::syn_options(&config, None);
+        assert_eq!(options.as_slice().len() % 4, 0);
+        let parsed = TcpOptions::parse(options.as_slice());
+        assert_eq!(parsed.mss, Some(1460));
+        assert_eq!(parsed.wscale, Some(3));
+        assert!(parsed.sack_permitted);
+        assert_eq!(parsed.timestamp.map(|(_, ecr)| ecr), Some(0));
+
+        let mut negotiated = NegotiatedOptions::new();
+        negotiated.negotiate(&config, &parsed);
+        assert!(negotiated.wscale_ok);
+        assert!(negotiated.sack_permitted);
+        assert!(negotiated.timestamps);
+        negotiated.ts_recent = 0xdead_beef;
+        let mut sack_blocks = SackBlocks::new();
+        for i in 0..4 {
+            sack_blocks.insert(1000 + 200 * i, 1100 + 200 * i);
+        }
+        // with timestamps only three blocks fit
+        let options = OptionBuf::options(&negotiated, &sack_blocks);
+        assert!(options.as_slice().len() <= MAX_OPTIONS_LEN);
+        let parsed = TcpOptions::parse(options.as_slice());
+        assert_eq!(parsed.timestamp.map(|(_, ecr)| ecr), Some(0xdead_beef));
+        assert_eq!(parsed.sack.as_slice(), &sack_blocks.as_slice()[..3]);
+    }
+
+    #[test]
+    fn options_without_negotiated_features() {
+        let config = TcpOptionConfig::new(1460, 5840, false, false, false, false);
+        assert_eq!(config.wscale, None);
+        assert_eq!(OptionBuf::syn_options(&config, None).as_slice(), &[2, 4, 0x05, 0xb4]);
+        let mut negotiated = NegotiatedOptions::new();
+        negotiated.negotiate(&config, &TcpOptions::parse(&[4, 2, 3, 3, 7]));
+        assert_eq!(negotiated.mss, DEFAULT_PEER_MSS);
+        assert!(!negotiated.wscale_ok);
+        assert!(!negotiated.sack_permitted);
+        let mut sack_blocks = SackBlocks::new();
+        sack_blocks.insert(1000, 1100);
+        assert!(OptionBuf::options(&negotiated, &sack_blocks).as_slice().is_empty());
+    }
+
+    #[test]
+    fn sack_blocks_merge_newest_first() {
+        let mut blocks = SackBlocks::new();
+        blocks.insert(1000, 1100);
+        blocks.insert(1200, 1300);
+        assert_eq!(blocks.as_slice(), &[(1200, 1300), (1000, 1100)]);
+        // adjoining blocks are merged
+        blocks.insert(1100, 1200);
+        assert_eq!(blocks.as_slice(), &[(1000, 1300)]);
+        for i in 0..5 {
+            blocks.insert(2000 + 200 * i, 2100 + 200 * i);
+        }
+        // the oldest blocks are dropped
+        assert_eq!(blocks.as_slice(), &[(2800, 2900), (2600, 2700), (2400, 2500), (2200, 2300)]);
+        assert!(blocks.covers(2610, 2700));
+        assert!(!blocks.covers(2650, 2750));
+        assert!(!blocks.covers(1000, 1100));
+    }
+
+    #[test]
+    fn sack_blocks_remove_acked() {
+        let mut blocks = SackBlocks::new();
+        blocks.insert(0xffff_ff00, 0xffff_ff80);
+        blocks.insert(0x100, 0x200);
+        blocks.insert(0x10, 0x80);
+        blocks.remove_acked(0x80);
+        assert_eq!(blocks.as_slice(), &[(0x100, 0x200)]);
+        // a partially acknowledged block is kept
+        blocks.remove_acked(0x180);
+        assert_eq!(blocks.as_slice(), &[(0x100, 0x200)]);
+        blocks.remove_acked(0x200);
+        assert!(blocks.is_empty());
+    }
+
+    #[test]
+    fn fast_open_cookie_lengths() {
+        let request = TcpOptions::parse(&[34, 2]).fast_open.unwrap();
+        assert!(request.is_request());
+        let cookie = TcpOptions::parse(&[34, 10, 1, 2, 3, 4, 5, 6, 7, 8]).fast_open.unwrap();
+        assert_eq!(cookie.as_slice(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+        // odd, too short and too long cookies are ignored
+        assert_eq!(TcpOptions::parse(&[34, 7, 1, 2, 3, 4, 5]).fast_open, None);
+        assert_eq!(TcpOptions::parse(&[34, 4, 1, 2]).fast_open, None);
+        assert_eq!(TcpOptions::parse(&[34, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).fast_open, None);
+
+        let mut options = OptionBuf::new();
+        options.push_mss(1460);
+        assert!(options.push_fast_open(&cookie));
+        assert_eq!(options.as_slice().len() % 4, 0);
+        assert_eq!(TcpOptions::parse(options.as_slice()).fast_open, Some(cookie));
+        let long_cookie = FastOpenCookie::new(&[0xaa; 16]);
+        assert!(options.push_fast_open(&long_cookie));
+        assert!(!options.push_fast_open(&long_cookie));
+    }
+}